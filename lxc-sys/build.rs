@@ -1,13 +1,78 @@
+#[cfg(feature = "regenerate")]
 extern crate bindgen;
+extern crate pkg_config;
 
 use std::env;
+
+#[cfg(feature = "regenerate")]
+use std::fs::File;
+
+#[cfg(feature = "regenerate")]
+use std::io::Read;
+
+#[cfg(feature = "regenerate")]
 use std::path::PathBuf;
 
-fn main() {
-    println!("cargo:rustc-link-lib=lxc");
+/// Determine the version of liblxc the submodule's headers belong
+/// to, based on the version definitions of the lxc source tree.
+#[cfg(feature = "regenerate")]
+fn submodule_version() -> Option<String> {
+    let mut configure = String::new();
+    File::open("lxc/configure.ac").ok()?.read_to_string(&mut configure).ok()?;
+
+    let define = |name: &str| -> Option<String> {
+        let prefix = format!("m4_define([{}],", name);
+        let line = configure.lines().find(|l| l.trim().starts_with(&prefix))?;
+
+        let value = line.trim()[prefix.len()..].trim().trim_end_matches(')').trim();
+        Some(value.to_string())
+    };
+
+    Some(format!("{}.{}.{}",
+        define("lxc_version_major")?,
+        define("lxc_version_minor")?,
+        define("lxc_version_micro")?))
+}
+
+/// Version of liblxc the pre-generated bindings selected by the
+/// enabled features belong to.
+#[cfg(not(feature = "regenerate"))]
+fn pregenerated_version() -> &'static str {
+    if env::var("CARGO_FEATURE_V4_0").is_ok() {
+        "4.0.0"
+    }
+    else if env::var("CARGO_FEATURE_V3_0").is_ok() {
+        "3.0.0"
+    }
+    else {
+        "2.1.0"
+    }
+}
+
+#[cfg(feature = "regenerate")]
+fn generate(library: Option<&pkg_config::Library>) -> Option<String> {
+    // Prefer the headers of the installed liblxc, and fall back
+    // to the ones of the lxc submodule
+    let installed = library.and_then(|lib| {
+        lib.include_paths.iter()
+            .map(|path| path.join("lxc").join("lxccontainer.h"))
+            .find(|header| header.exists())
+    });
 
+    let (header, version) = match installed {
+        Some(header) => (header, library.map(|lib| lib.version.clone())),
+        None => (PathBuf::from("wrapper.h"), submodule_version())
+    };
+
+    // Same options as the command recorded in the pre-generated
+    // bindings, which the lxc crate relies on
     let bindings = bindgen::Builder::default()
-        .header("wrapper.h")
+        .header(header.to_str().unwrap())
+        .ignore_functions()
+        .layout_tests(false)
+        .allowlist_type("lxc_.*|bdev_specs|migrate_opts")
+        .allowlist_var("LXC_.*|MIGRATE_.*|FEATURE_.*")
+        .rustified_enum("lxc_attach_env_policy_t")
         .generate()
         .expect("Failed to generate bindings");
 
@@ -15,4 +80,30 @@ fn main() {
 
     bindings.write_to_file(out_path.join("bindings.rs"))
         .expect("Failed to write bindings");
+
+    version
+}
+
+#[cfg(not(feature = "regenerate"))]
+fn generate(_: Option<&pkg_config::Library>) -> Option<String> {
+    Some(pregenerated_version().to_string())
+}
+
+fn main() {
+    let dlopen = env::var("CARGO_FEATURE_DLOPEN").is_ok();
+
+    // When loading liblxc at runtime, the functions are resolved
+    // by the dynamic module instead of the linker
+    let library = pkg_config::Config::new()
+        .cargo_metadata(!dlopen)
+        .probe("lxc")
+        .ok();
+
+    if library.is_none() && !dlopen {
+        println!("cargo:rustc-link-lib=lxc");
+    }
+
+    if let Some(version) = generate(library.as_ref()) {
+        println!("cargo:rustc-env=LXC_HEADER_VERSION={}", version);
+    }
 }
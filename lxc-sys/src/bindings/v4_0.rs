@@ -0,0 +1,271 @@
+// Pre-generated bindings for liblxc 4.0.0, based on the output of
+// rust-bindgen for its installed lxccontainer.h and maintained by
+// hand since. The `regenerate` feature runs the same options:
+//
+//   bindgen --ignore-functions --no-layout-tests \
+//       --allowlist-type 'lxc_.*|bdev_specs|migrate_opts' \
+//       --allowlist-var 'LXC_.*|MIGRATE_.*|FEATURE_.*' \
+//       --rustified-enum lxc_attach_env_policy_t \
+//       wrapper.h
+//
+// Functions are declared in `functions.rs`, so that they can be
+// resolved at runtime instead when the `dlopen` feature is enabled.
+
+pub type __uid_t = ::std::os::raw::c_uint;
+pub type __gid_t = ::std::os::raw::c_uint;
+pub type __pid_t = ::std::os::raw::c_int;
+pub type uid_t = __uid_t;
+pub type gid_t = __gid_t;
+pub type pid_t = __pid_t;
+pub const LXC_ATTACH_MOVE_TO_CGROUP: _bindgen_ty_1 = 1;
+pub const LXC_ATTACH_DROP_CAPABILITIES: _bindgen_ty_1 = 2;
+pub const LXC_ATTACH_SET_PERSONALITY: _bindgen_ty_1 = 4;
+pub const LXC_ATTACH_LSM_EXEC: _bindgen_ty_1 = 8;
+pub const LXC_ATTACH_REMOUNT_PROC_SYS: _bindgen_ty_1 = 65536;
+pub const LXC_ATTACH_LSM_NOW: _bindgen_ty_1 = 131072;
+pub const LXC_ATTACH_NO_NEW_PRIVS: _bindgen_ty_1 = 262144;
+pub const LXC_ATTACH_TERMINAL: _bindgen_ty_1 = 524288;
+pub const LXC_ATTACH_DEFAULT: _bindgen_ty_1 = 65535;
+pub type _bindgen_ty_1 = ::std::os::raw::c_uint;
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum lxc_attach_env_policy_t {
+    LXC_ATTACH_KEEP_ENV = 0,
+    LXC_ATTACH_CLEAR_ENV = 1,
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct lxc_attach_options_t {
+    pub attach_flags: ::std::os::raw::c_int,
+    pub namespaces: ::std::os::raw::c_int,
+    pub personality: ::std::os::raw::c_long,
+    pub initial_cwd: *mut ::std::os::raw::c_char,
+    pub uid: uid_t,
+    pub gid: gid_t,
+    pub env_policy: lxc_attach_env_policy_t,
+    pub extra_env_vars: *mut *mut ::std::os::raw::c_char,
+    pub extra_keep_env: *mut *mut ::std::os::raw::c_char,
+    pub stdin_fd: ::std::os::raw::c_int,
+    pub stdout_fd: ::std::os::raw::c_int,
+    pub stderr_fd: ::std::os::raw::c_int,
+    pub log_fd: ::std::os::raw::c_int,
+    pub lsm_label: *mut ::std::os::raw::c_char,
+}
+impl Clone for lxc_attach_options_t {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct lxc_attach_command_t {
+    pub program: *mut ::std::os::raw::c_char,
+    pub argv: *mut *mut ::std::os::raw::c_char,
+}
+impl Clone for lxc_attach_command_t {
+    fn clone(&self) -> Self { *self }
+}
+pub type lxc_attach_exec_t = ::std::option::Option<unsafe extern "C" fn(payload: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int>;
+pub const LXC_CLONE_KEEPNAME: u32 = 1;
+pub const LXC_CLONE_KEEPMACADDR: u32 = 2;
+pub const LXC_CLONE_SNAPSHOT: u32 = 4;
+pub const LXC_CLONE_KEEPBDEVTYPE: u32 = 8;
+pub const LXC_CLONE_MAYBE_SNAPSHOT: u32 = 16;
+pub const LXC_CLONE_ALLOW_RUNNING: u32 = 32;
+pub const LXC_CLONE_MAXFLAGS: u32 = 64;
+pub const FEATURE_MEM_TRACK: u32 = 1;
+pub const FEATURE_LAZY_PAGES: u32 = 2;
+pub const LXC_CREATE_QUIET: _bindgen_ty_2 = 1;
+pub const LXC_CREATE_MAXFLAGS: _bindgen_ty_2 = 2;
+pub type _bindgen_ty_2 = ::std::os::raw::c_uint;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct lxc_lock {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct lxc_conf {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct lxc_console_log {
+    pub clear: bool,
+    pub read: bool,
+    pub read_max: *mut u64,
+    pub data: *mut ::std::os::raw::c_char,
+}
+impl Clone for lxc_console_log {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct lxc_mount {
+    pub version: ::std::os::raw::c_int,
+}
+impl Clone for lxc_mount {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct lxc_container {
+    pub name: *mut ::std::os::raw::c_char,
+    pub configfile: *mut ::std::os::raw::c_char,
+    pub pidfile: *mut ::std::os::raw::c_char,
+    pub slock: *mut lxc_lock,
+    pub privlock: *mut lxc_lock,
+    pub numthreads: ::std::os::raw::c_int,
+    pub lxc_conf: *mut lxc_conf,
+    pub error_string: *mut ::std::os::raw::c_char,
+    pub error_num: ::std::os::raw::c_int,
+    pub daemonize: bool,
+    pub config_path: *mut ::std::os::raw::c_char,
+    pub is_defined: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub state: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> *const ::std::os::raw::c_char>,
+    pub is_running: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub freeze: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub unfreeze: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub init_pid: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> pid_t>,
+    pub load_config: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, alt_file: *const ::std::os::raw::c_char) -> bool>,
+    pub start: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, useinit: ::std::os::raw::c_int, argv: *const *const ::std::os::raw::c_char) -> bool>,
+    pub startl: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, useinit: ::std::os::raw::c_int, ...) -> bool>,
+    pub stop: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub want_daemonize: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, state: bool) -> bool>,
+    pub want_close_all_fds: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, state: bool) -> bool>,
+    pub config_file_name: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> *mut ::std::os::raw::c_char>,
+    pub wait: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, state: *const ::std::os::raw::c_char, timeout: ::std::os::raw::c_int) -> bool>,
+    pub set_config_item: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, key: *const ::std::os::raw::c_char, value: *const ::std::os::raw::c_char) -> bool>,
+    pub destroy: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub save_config: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, alt_file: *const ::std::os::raw::c_char) -> bool>,
+    pub create: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, t: *const ::std::os::raw::c_char, bdevtype: *const ::std::os::raw::c_char, specs: *mut bdev_specs, flags: ::std::os::raw::c_int, argv: *const *const ::std::os::raw::c_char) -> bool>,
+    pub createl: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, t: *const ::std::os::raw::c_char, bdevtype: *const ::std::os::raw::c_char, specs: *mut bdev_specs, flags: ::std::os::raw::c_int, ...) -> bool>,
+    pub rename: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, newname: *const ::std::os::raw::c_char) -> bool>,
+    pub reboot: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub shutdown: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, timeout: ::std::os::raw::c_int) -> bool>,
+    pub clear_config: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container)>,
+    pub clear_config_item: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, key: *const ::std::os::raw::c_char) -> bool>,
+    pub get_config_item: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, key: *const ::std::os::raw::c_char, retv: *mut ::std::os::raw::c_char, inlen: ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+    pub get_running_config_item: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, key: *const ::std::os::raw::c_char) -> *mut ::std::os::raw::c_char>,
+    pub get_keys: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, key: *const ::std::os::raw::c_char, retv: *mut ::std::os::raw::c_char, inlen: ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+    pub get_interfaces: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> *mut *mut ::std::os::raw::c_char>,
+    pub get_ips: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, interface: *const ::std::os::raw::c_char, family: *const ::std::os::raw::c_char, scope: ::std::os::raw::c_int) -> *mut *mut ::std::os::raw::c_char>,
+    pub get_cgroup_item: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, subsys: *const ::std::os::raw::c_char, retv: *mut ::std::os::raw::c_char, inlen: ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+    pub set_cgroup_item: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, subsys: *const ::std::os::raw::c_char, value: *const ::std::os::raw::c_char) -> bool>,
+    pub get_config_path: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> *const ::std::os::raw::c_char>,
+    pub set_config_path: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, path: *const ::std::os::raw::c_char) -> bool>,
+    pub clone: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, newname: *const ::std::os::raw::c_char, lxcpath: *const ::std::os::raw::c_char, flags: ::std::os::raw::c_int, bdevtype: *const ::std::os::raw::c_char, bdevdata: *const ::std::os::raw::c_char, newsize: u64, hookargs: *mut *mut ::std::os::raw::c_char) -> *mut lxc_container>,
+    pub console_getfd: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, ttynum: *mut ::std::os::raw::c_int, masterfd: *mut ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+    pub console: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, ttynum: ::std::os::raw::c_int, stdinfd: ::std::os::raw::c_int, stdoutfd: ::std::os::raw::c_int, stderrfd: ::std::os::raw::c_int, escape: ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+    pub attach: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, exec_function: lxc_attach_exec_t, exec_payload: *mut ::std::os::raw::c_void, options: *mut lxc_attach_options_t, attached_process: *mut pid_t) -> ::std::os::raw::c_int>,
+    pub attach_run_wait: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, options: *mut lxc_attach_options_t, program: *const ::std::os::raw::c_char, argv: *const *const ::std::os::raw::c_char) -> ::std::os::raw::c_int>,
+    pub attach_run_waitl: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, options: *mut lxc_attach_options_t, program: *const ::std::os::raw::c_char, arg: *const ::std::os::raw::c_char, ...) -> ::std::os::raw::c_int>,
+    pub snapshot: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, commentfile: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int>,
+    pub snapshot_list: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, snapshots: *mut *mut lxc_snapshot) -> ::std::os::raw::c_int>,
+    pub snapshot_restore: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, snapname: *const ::std::os::raw::c_char, newname: *const ::std::os::raw::c_char) -> bool>,
+    pub snapshot_destroy: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, snapname: *const ::std::os::raw::c_char) -> bool>,
+    pub may_control: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub add_device_node: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, src_path: *const ::std::os::raw::c_char, dest_path: *const ::std::os::raw::c_char) -> bool>,
+    pub remove_device_node: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, src_path: *const ::std::os::raw::c_char, dest_path: *const ::std::os::raw::c_char) -> bool>,
+    pub attach_interface: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, dev: *const ::std::os::raw::c_char, dst_dev: *const ::std::os::raw::c_char) -> bool>,
+    pub detach_interface: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, dev: *const ::std::os::raw::c_char, dst_dev: *const ::std::os::raw::c_char) -> bool>,
+    pub checkpoint: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, directory: *mut ::std::os::raw::c_char, stop: bool, verbose: bool) -> bool>,
+    pub restore: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, directory: *mut ::std::os::raw::c_char, verbose: bool) -> bool>,
+    pub destroy_with_snapshots: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub snapshot_destroy_all: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> bool>,
+    pub migrate: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, cmd: ::std::os::raw::c_uint, opts: *mut migrate_opts, size: ::std::os::raw::c_uint) -> ::std::os::raw::c_int>,
+    pub console_log: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, log: *mut lxc_console_log) -> ::std::os::raw::c_int>,
+    pub reboot2: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, timeout: ::std::os::raw::c_int) -> bool>,
+    pub mount: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, source: *const ::std::os::raw::c_char, target: *const ::std::os::raw::c_char, filesystemtype: *const ::std::os::raw::c_char, mountflags: ::std::os::raw::c_ulong, data: *const ::std::os::raw::c_void, mnt: *mut lxc_mount) -> ::std::os::raw::c_int>,
+    pub umount: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container, target: *const ::std::os::raw::c_char, mountflags: ::std::os::raw::c_ulong, mnt: *mut lxc_mount) -> ::std::os::raw::c_int>,
+    pub seccomp_notify_fd: ::std::option::Option<unsafe extern "C" fn(c: *mut lxc_container) -> ::std::os::raw::c_int>,
+}
+impl Clone for lxc_container {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct lxc_snapshot {
+    pub name: *mut ::std::os::raw::c_char,
+    pub comment_pathname: *mut ::std::os::raw::c_char,
+    pub timestamp: *mut ::std::os::raw::c_char,
+    pub lxcpath: *mut ::std::os::raw::c_char,
+    pub free: ::std::option::Option<unsafe extern "C" fn(s: *mut lxc_snapshot)>,
+}
+impl Clone for lxc_snapshot {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct bdev_specs__bindgen_ty_1 {
+    pub zfsroot: *mut ::std::os::raw::c_char,
+}
+impl Clone for bdev_specs__bindgen_ty_1 {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct bdev_specs__bindgen_ty_2 {
+    pub vg: *mut ::std::os::raw::c_char,
+    pub lv: *mut ::std::os::raw::c_char,
+    pub thinpool: *mut ::std::os::raw::c_char,
+}
+impl Clone for bdev_specs__bindgen_ty_2 {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct bdev_specs__bindgen_ty_3 {
+    pub rbdname: *mut ::std::os::raw::c_char,
+    pub rbdpool: *mut ::std::os::raw::c_char,
+}
+impl Clone for bdev_specs__bindgen_ty_3 {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct bdev_specs {
+    pub fstype: *mut ::std::os::raw::c_char,
+    pub fssize: u64,
+    pub zfs: bdev_specs__bindgen_ty_1,
+    pub lvm: bdev_specs__bindgen_ty_2,
+    pub dir: *mut ::std::os::raw::c_char,
+    pub rbd: bdev_specs__bindgen_ty_3,
+}
+impl Clone for bdev_specs {
+    fn clone(&self) -> Self { *self }
+}
+pub const MIGRATE_PRE_DUMP: _bindgen_ty_3 = 0;
+pub const MIGRATE_DUMP: _bindgen_ty_3 = 1;
+pub const MIGRATE_RESTORE: _bindgen_ty_3 = 2;
+pub const MIGRATE_FEATURE_CHECK: _bindgen_ty_3 = 3;
+pub type _bindgen_ty_3 = ::std::os::raw::c_uint;
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct migrate_opts {
+    pub directory: *mut ::std::os::raw::c_char,
+    pub verbose: bool,
+    pub stop: bool,
+    pub predump_dir: *mut ::std::os::raw::c_char,
+    pub pageserver_address: *mut ::std::os::raw::c_char,
+    pub pageserver_port: *mut ::std::os::raw::c_char,
+    pub preserves_inodes: bool,
+    pub action_script: *mut ::std::os::raw::c_char,
+    pub disable_skip_in_flight: bool,
+    pub ghost_limit: u64,
+    pub features_to_check: u64,
+}
+impl Clone for migrate_opts {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct lxc_log {
+    pub name: *const ::std::os::raw::c_char,
+    pub lxcpath: *const ::std::os::raw::c_char,
+    pub file: *const ::std::os::raw::c_char,
+    pub level: *const ::std::os::raw::c_char,
+    pub prefix: *const ::std::os::raw::c_char,
+    pub quiet: bool,
+}
+impl Clone for lxc_log {
+    fn clone(&self) -> Self { *self }
+}
@@ -0,0 +1,119 @@
+//! Runtime loading of liblxc. The functions defined here have the
+//! same signatures as the ones bindgen generates when linking to
+//! liblxc, and forward the calls to the symbols resolved by `load`.
+
+use libloading::Library;
+use std::io;
+use std::ptr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use super::{lxc_container, lxc_log};
+
+/// Shared object names tried, in order, when loading liblxc.
+const LIBRARY_NAMES: &[&str] = &["liblxc.so.1", "liblxc.so"];
+
+/// Loaded function table, null until `load` succeeds.
+static FUNCTIONS: AtomicPtr<Functions> = AtomicPtr::new(ptr::null_mut());
+
+macro_rules! functions {
+    ($(fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        /// Symbols resolved from the loaded liblxc. Symbols missing
+        /// from older liblxc versions are `None`.
+        struct Functions {
+            $($name: Option<unsafe extern "C" fn($($ty),*) -> $ret>,)*
+            _library: Library
+        }
+
+        impl Functions {
+            fn resolve(library: Library) -> Functions {
+                unsafe {
+                    Functions {
+                        $($name: library
+                            .get::<unsafe extern "C" fn($($ty),*) -> $ret>(
+                                concat!(stringify!($name), "\0").as_bytes())
+                            .ok()
+                            .map(|sym| *sym),)*
+                        _library: library
+                    }
+                }
+            }
+        }
+
+        $(pub unsafe extern "C" fn $name($($arg: $ty),*) -> $ret {
+            let func = functions().$name
+                .expect(concat!("liblxc does not provide ", stringify!($name)));
+
+            func($($arg),*)
+        })*
+    };
+}
+
+functions! {
+    fn lxc_container_new(name: *const c_char, configpath: *const c_char) -> *mut lxc_container;
+    fn lxc_container_get(c: *mut lxc_container) -> c_int;
+    fn lxc_container_put(c: *mut lxc_container) -> c_int;
+    fn lxc_get_wait_states(states: *mut *const c_char) -> c_int;
+    fn lxc_get_global_config_item(key: *const c_char) -> *const c_char;
+    fn lxc_get_version() -> *const c_char;
+    fn list_defined_containers(lxcpath: *const c_char, names: *mut *mut *mut c_char, cret: *mut *mut *mut lxc_container) -> c_int;
+    fn list_active_containers(lxcpath: *const c_char, names: *mut *mut *mut c_char, cret: *mut *mut *mut lxc_container) -> c_int;
+    fn list_all_containers(lxcpath: *const c_char, names: *mut *mut *mut c_char, cret: *mut *mut *mut lxc_container) -> c_int;
+    fn lxc_log_init(log: *mut lxc_log) -> c_int;
+    fn lxc_log_close() -> ();
+    fn lxc_config_item_is_supported(key: *const c_char) -> bool;
+    fn lxc_has_api_extension(extension: *const c_char) -> bool;
+    fn lxc_attach_run_command(payload: *mut c_void) -> c_int;
+    fn lxc_attach_run_shell(payload: *mut c_void) -> c_int;
+}
+
+/// Get the loaded function table, loading liblxc if needed. Panics
+/// if liblxc cannot be loaded.
+fn functions() -> &'static Functions {
+    if let Err(e) = load() {
+        panic!("failed to load liblxc: {}", e);
+    }
+
+    unsafe { &*FUNCTIONS.load(Ordering::Acquire) }
+}
+
+/// Load liblxc and resolve its symbols. Calling this function
+/// again once liblxc has been loaded does nothing.
+pub fn load() -> io::Result<()> {
+    if is_loaded() {
+        return Ok(());
+    }
+
+    let mut error = io::Error::new(io::ErrorKind::NotFound, "liblxc not found");
+    let mut library = None;
+
+    for name in LIBRARY_NAMES {
+        match Library::new(name) {
+            Ok(lib) => {
+                library = Some(lib);
+                break;
+            },
+            Err(e) => error = e
+        }
+    }
+
+    let functions = Functions::resolve(library.ok_or(error)?);
+
+    if functions.lxc_container_new.is_none() || functions.lxc_get_version.is_none() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid liblxc: missing symbols"));
+    }
+
+    let ptr = Box::into_raw(Box::new(functions));
+
+    // Another thread may have loaded the library in the meantime
+    if FUNCTIONS.compare_exchange(ptr::null_mut(), ptr, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        unsafe { drop(Box::from_raw(ptr)); }
+    }
+
+    Ok(())
+}
+
+/// Check wether liblxc has been loaded.
+pub fn is_loaded() -> bool {
+    !FUNCTIONS.load(Ordering::Acquire).is_null()
+}
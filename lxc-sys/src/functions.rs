@@ -0,0 +1,23 @@
+// Functions of liblxc's public API, linked at build time. The
+// declarations follow the signatures bindgen generates.
+
+use super::*;
+
+extern "C" {
+    pub fn lxc_container_new(name: *const ::std::os::raw::c_char, configpath: *const ::std::os::raw::c_char) -> *mut lxc_container;
+    pub fn lxc_container_get(c: *mut lxc_container) -> ::std::os::raw::c_int;
+    pub fn lxc_container_put(c: *mut lxc_container) -> ::std::os::raw::c_int;
+    pub fn lxc_get_wait_states(states: *mut *const ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+    pub fn lxc_get_global_config_item(key: *const ::std::os::raw::c_char) -> *const ::std::os::raw::c_char;
+    pub fn lxc_get_version() -> *const ::std::os::raw::c_char;
+    pub fn list_defined_containers(lxcpath: *const ::std::os::raw::c_char, names: *mut *mut *mut ::std::os::raw::c_char, cret: *mut *mut *mut lxc_container) -> ::std::os::raw::c_int;
+    pub fn list_active_containers(lxcpath: *const ::std::os::raw::c_char, names: *mut *mut *mut ::std::os::raw::c_char, cret: *mut *mut *mut lxc_container) -> ::std::os::raw::c_int;
+    pub fn list_all_containers(lxcpath: *const ::std::os::raw::c_char, names: *mut *mut *mut ::std::os::raw::c_char, cret: *mut *mut *mut lxc_container) -> ::std::os::raw::c_int;
+    pub fn lxc_log_init(log: *mut lxc_log) -> ::std::os::raw::c_int;
+    pub fn lxc_log_close();
+    pub fn lxc_config_item_is_supported(key: *const ::std::os::raw::c_char) -> bool;
+    #[cfg(feature = "v3_0")]
+    pub fn lxc_has_api_extension(extension: *const ::std::os::raw::c_char) -> bool;
+    pub fn lxc_attach_run_command(payload: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+    pub fn lxc_attach_run_shell(payload: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+}
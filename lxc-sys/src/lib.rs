@@ -1,5 +1,37 @@
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
+#![allow(clippy::missing_safety_doc)]
 
+// Bindings regenerated from the liblxc headers at build time
+#[cfg(feature = "regenerate")]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+// Pre-generated bindings, the newest selected version wins
+#[cfg(all(not(feature = "regenerate"), feature = "v4_0"))]
+include!("bindings/v4_0.rs");
+
+#[cfg(all(not(feature = "regenerate"), feature = "v3_0", not(feature = "v4_0")))]
+include!("bindings/v3_0.rs");
+
+#[cfg(all(not(feature = "regenerate"), not(feature = "v3_0")))]
+include!("bindings/v2_1.rs");
+
+#[cfg(feature = "dlopen")]
+extern crate libloading;
+
+#[cfg(not(feature = "dlopen"))]
+mod functions;
+
+#[cfg(not(feature = "dlopen"))]
+pub use functions::*;
+
+#[cfg(feature = "dlopen")]
+mod dynamic;
+
+#[cfg(feature = "dlopen")]
+pub use dynamic::*;
+
+/// Version of liblxc the bindings were generated from, if it
+/// could be determined at build time.
+pub const HEADER_VERSION: Option<&'static str> = option_env!("LXC_HEADER_VERSION");
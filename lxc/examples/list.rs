@@ -3,10 +3,10 @@ extern crate lxc;
 use lxc::Container;
 
 fn main() {
-	let lxcpath = "/var/lib/lxc";
-	let containers = Container::list(lxcpath).unwrap();
+	let lxcpath = lxc::default_lxcpath().unwrap();
+	let containers = Container::list(&lxcpath).unwrap();
 
 	for c in containers {
-		println!("{} - {}", c.name, c.state());
+		println!("{} - {}", c.name, c.state().unwrap());
 	}	
 }
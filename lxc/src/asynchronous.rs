@@ -0,0 +1,175 @@
+//! Asynchronous wrapper around `Container`, for use from tokio.
+//!
+//! The long-running liblxc operations are run on tokio's blocking
+//! thread pool. Waiting is done in short slices, so that dropping
+//! a pending future stops the wait shortly after.
+
+use std::time::{Duration, Instant};
+
+use super::{Container, Error, Result, State, Template};
+
+/// Duration of each blocking wait, in seconds. Bounds the time a
+/// cancelled wait keeps running on the blocking pool.
+const WAIT_SLICE: i32 = 1;
+
+/// Bounds of the pause between two waits, leaving room on the
+/// blocking pool to the other operations.
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Run a blocking liblxc operation on tokio's blocking thread pool.
+async fn blocking<F, T>(func: F) -> Result<T>
+    where F: FnOnce() -> Result<T> + Send + 'static, T: Send + 'static
+{
+    tokio::task::spawn_blocking(func)
+        .await
+        .unwrap_or(Err(Error::OperationFailed))
+}
+
+/// Asynchronous handle to an LXC container. Must be used from
+/// within a tokio runtime.
+#[derive(Debug, Clone)]
+pub struct AsyncContainer {
+    inner: Container
+}
+
+impl AsyncContainer {
+    /// Create an AsyncContainer object from a Container.
+    pub fn new(container: Container) -> AsyncContainer {
+        AsyncContainer {
+            inner: container
+        }
+    }
+
+    /// Get the underlying Container, to perform quick
+    /// synchronous operations.
+    pub fn container(&self) -> &Container {
+        &self.inner
+    }
+
+    /// Get back the underlying Container.
+    pub fn into_inner(self) -> Container {
+        self.inner
+    }
+
+    /// Create a new LXC container.
+    pub async fn create(lxcpath: &str, name: &str, template: Template) -> Result<AsyncContainer> {
+        let lxcpath = lxcpath.to_string();
+        let name = name.to_string();
+
+        blocking(move || Container::create(&lxcpath, &name, template))
+            .await
+            .map(AsyncContainer::new)
+    }
+
+    /// Start the LXC container.
+    pub async fn start(&self) -> Result<()> {
+        let ct = self.inner.clone();
+        blocking(move || ct.start()).await
+    }
+
+    /// Stop the LXC container.
+    pub async fn stop(&self) -> Result<()> {
+        let ct = self.inner.clone();
+        blocking(move || ct.stop()).await
+    }
+
+    /// Request the container to shutdown. Returns an error
+    /// if the container failed to shutdown in the
+    /// given time.
+    pub async fn shutdown(&self, timeout: i32) -> Result<()> {
+        let ct = self.inner.clone();
+        blocking(move || ct.shutdown(timeout)).await
+    }
+
+    /// Copy the container into a new container with the given
+    /// name, in the specified lxcpath.
+    pub async fn clone_to(&self, name: &str, lxcpath: &str) -> Result<AsyncContainer> {
+        let ct = self.inner.clone();
+        let name = name.to_string();
+        let lxcpath = lxcpath.to_string();
+
+        blocking(move || ct.clone_to(&name, &lxcpath))
+            .await
+            .map(AsyncContainer::new)
+    }
+
+    /// Create a LXC container snapshot with the given path
+    /// to the snapshot's comment file. Returns the
+    /// zero-based snapshot number.
+    pub async fn snapshot(&self, comment_file: Option<&str>) -> Result<u32> {
+        let ct = self.inner.clone();
+        let comment_file = comment_file.map(|s| s.to_string());
+
+        blocking(move || ct.snapshot(comment_file.as_deref())).await
+    }
+
+    /// Checkpoint an LXC container. Dump the checkpoint files in the
+    /// specified directory. There is the possibility to stop the
+    /// container after the ckeckpoint is done.
+    pub async fn checkpoint(&self, directory: &str, stop: bool, verbose: bool) -> Result<()> {
+        let ct = self.inner.clone();
+        let directory = directory.to_string();
+
+        blocking(move || ct.checkpoint(&directory, stop, verbose)).await
+    }
+
+    /// Wait for the container to reach the given state. Returns an
+    /// error if the state was not reached within the timeout, if
+    /// any, if the state is invalid or if the container is not
+    /// defined. Dropping the returned future cancels the wait.
+    pub async fn wait(&self, state: &str, timeout: Option<Duration>) -> Result<()> {
+        state.parse::<State>()?;
+
+        if !self.inner.is_defined()? {
+            return Err(Error::ContainerDoesNotExists);
+        }
+
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut backoff = MIN_BACKOFF;
+
+        loop {
+            let ct = self.inner.clone();
+            let state = state.to_string();
+            let started = Instant::now();
+
+            if blocking(move || ct.wait(&state, WAIT_SLICE)).await.is_ok() {
+                return Ok(());
+            }
+
+            // liblxc only gives up before the end of the slice when
+            // it cannot wait at all
+            if started.elapsed() < Duration::from_secs(WAIT_SLICE as u64) / 2 {
+                return Err(Error::OperationFailed);
+            }
+
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(Error::OperationFailed);
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Request the container to shutdown, and stop it if it is
+    /// still running after the given time. Dropping the returned
+    /// future cancels the wait for the shutdown.
+    pub async fn shutdown_or_stop(&self, timeout: Duration) -> Result<()> {
+        // Only send the shutdown request, waiting is done below. If it
+        // could not be sent, the container is stopped after the wait
+        let _ = self.shutdown(0).await;
+
+        if self.wait("STOPPED", Some(timeout)).await.is_ok() {
+            return Ok(());
+        }
+
+        self.stop().await
+    }
+}
+
+impl From<Container> for AsyncContainer {
+    fn from(container: Container) -> AsyncContainer {
+        AsyncContainer::new(container)
+    }
+}
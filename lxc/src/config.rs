@@ -0,0 +1,76 @@
+//! Access to the host-wide LXC configuration (`lxc.conf`).
+
+use crate::lib;
+use std::ffi::{CStr, CString};
+
+/// Read-only view of the host-wide LXC configuration. Unset items
+/// resolve to liblxc's built-in defaults, which take the current
+/// user into account (unprivileged users get paths under their
+/// home directory).
+pub struct GlobalConfig;
+
+impl GlobalConfig {
+    /// Retrieve the value of any global configuration item by its
+    /// key. Returns `None` if the key is unknown to liblxc.
+    pub fn get(key: &str) -> Option<String> {
+        unsafe {
+            let key = CString::new(key).unwrap();
+            let value = lib::lxc_get_global_config_item(key.as_ptr());
+
+            if value.is_null() {
+                return None;
+            }
+
+            Some(CStr::from_ptr(value).to_string_lossy().into_owned())
+        }
+    }
+
+    /// Default path where containers are stored (`lxc.lxcpath`).
+    pub fn lxcpath() -> Option<String> {
+        GlobalConfig::get("lxc.lxcpath")
+    }
+
+    /// Configuration file applied to newly created containers
+    /// (`lxc.default_config`).
+    pub fn default_config() -> Option<String> {
+        GlobalConfig::get("lxc.default_config")
+    }
+
+    /// LVM volume group used for LVM backed containers
+    /// (`lxc.bdev.lvm.vg`).
+    pub fn lvm_vg() -> Option<String> {
+        GlobalConfig::get("lxc.bdev.lvm.vg")
+    }
+
+    /// LVM thin pool used for LVM backed containers
+    /// (`lxc.bdev.lvm.thin_pool`).
+    pub fn lvm_thin_pool() -> Option<String> {
+        GlobalConfig::get("lxc.bdev.lvm.thin_pool")
+    }
+
+    /// ZFS dataset under which ZFS backed containers are
+    /// created (`lxc.bdev.zfs.root`).
+    pub fn zfs_root() -> Option<String> {
+        GlobalConfig::get("lxc.bdev.zfs.root")
+    }
+
+    /// Cgroup controllers LXC is allowed to use (`lxc.cgroup.use`).
+    /// An empty list means no restriction was configured.
+    pub fn cgroup_use() -> Vec<String> {
+        GlobalConfig::get("lxc.cgroup.use")
+            .map(|value| {
+                value.split(',')
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Pattern used to name the containers' cgroups
+    /// (`lxc.cgroup.pattern`).
+    pub fn cgroup_pattern() -> Option<String> {
+        GlobalConfig::get("lxc.cgroup.pattern")
+    }
+}
@@ -0,0 +1,495 @@
+//! Options of liblxc's `create` call: the backing store of the
+//! container's rootfs, the creation flags and the handling of the
+//! template's output.
+
+use crate::lib;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use libc::{c_char, c_int};
+
+use super::{Error, Result};
+
+/// Configuration key holding the path of a container's rootfs.
+/// liblxc 2.1 already understands it, and 3.0 removed the legacy
+/// `lxc.rootfs`.
+pub(crate) const ROOTFS_KEY: &str = "lxc.rootfs.path";
+
+/// Serializes the redirections of the process' stdout and stderr.
+static OUTPUT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Backing store holding a container's rootfs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BackingStore {
+    /// Plain directory.
+    Dir,
+
+    /// Btrfs subvolume.
+    Btrfs,
+
+    /// ZFS dataset.
+    Zfs,
+
+    /// LVM logical volume.
+    Lvm,
+
+    /// Loop-mounted image file.
+    Loop,
+
+    /// Overlay filesystem.
+    Overlay,
+
+    /// Ceph RADOS block device.
+    Rbd
+}
+
+impl BackingStore {
+    /// Name of the backing store, as used by liblxc.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            BackingStore::Dir => "dir",
+            BackingStore::Btrfs => "btrfs",
+            BackingStore::Zfs => "zfs",
+            BackingStore::Lvm => "lvm",
+            BackingStore::Loop => "loop",
+            BackingStore::Overlay => "overlay",
+            BackingStore::Rbd => "rbd"
+        }
+    }
+}
+
+/// Rootfs built outside of LXC, used to populate a new container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rootfs {
+    /// Directory whose content is copied into the container's rootfs.
+    Directory(String),
+
+    /// Tarball extracted into the container's rootfs. Compressed
+    /// tarballs (gzip, xz, zstd) are detected by `tar`.
+    Tarball(String)
+}
+
+impl Rootfs {
+    /// Copy or extract the rootfs into the given rootfs path, as
+    /// found in a container's configuration.
+    pub(crate) fn extract_to(&self, rootfs_path: &str) -> Result<()> {
+        let path = rootfs_dir(rootfs_path);
+
+        if !Path::new(path).is_dir() {
+            return Err(Error::Unsupported);
+        }
+
+        let status = match *self {
+            Rootfs::Directory(ref dir) => Command::new("cp")
+                .arg("-a")
+                .arg(format!("{}/.", dir))
+                .arg(path)
+                .status(),
+
+            Rootfs::Tarball(ref file) => Command::new("tar")
+                .arg("--numeric-owner")
+                .arg("-xpf")
+                .arg(file)
+                .arg("-C")
+                .arg(path)
+                .status()
+        };
+
+        match status {
+            Ok(status) if status.success() => Ok(()),
+            _ => Err(Error::OperationFailed)
+        }
+    }
+}
+
+/// Get the directory of a rootfs path, as found in a container's
+/// configuration.
+pub(crate) fn rootfs_dir(rootfs_path: &str) -> &str {
+    // Strip the backing store prefix, e.g. "dir:"
+    let path = match rootfs_path.find(':') {
+        Some(i) if rootfs_path[i + 1..].starts_with('/') => &rootfs_path[i + 1..],
+        _ => rootfs_path
+    };
+
+    // Overlay rootfs paths are "lower:upper", the upper one is
+    // the container's
+    path.rsplit(':').next().unwrap_or(path)
+}
+
+/// Callback receiving each line written by a template script.
+#[derive(Clone)]
+struct OutputHandler(Arc<dyn Fn(&str) + Send + Sync>);
+
+impl fmt::Debug for OutputHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OutputHandler")
+    }
+}
+
+/// Options used when creating a container. Unset specs fall back
+/// to liblxc's defaults and to the global configuration (see
+/// `GlobalConfig`).
+#[derive(Debug, Clone)]
+pub struct CreateOptions {
+    backing_store: Option<BackingStore>,
+    fstype: Option<String>,
+    fssize: u64,
+    zfs_root: Option<String>,
+    lvm_vg: Option<String>,
+    lvm_lv: Option<String>,
+    lvm_thin_pool: Option<String>,
+    rbd_pool: Option<String>,
+    rbd_name: Option<String>,
+    dir: Option<String>,
+    quiet: bool,
+    output: Option<OutputHandler>
+}
+
+impl Default for CreateOptions {
+    fn default() -> CreateOptions {
+        CreateOptions {
+            backing_store: None,
+            fstype: None,
+            fssize: 0,
+            zfs_root: None,
+            lvm_vg: None,
+            lvm_lv: None,
+            lvm_thin_pool: None,
+            rbd_pool: None,
+            rbd_name: None,
+            dir: None,
+            quiet: true,
+            output: None
+        }
+    }
+}
+
+impl CreateOptions {
+    /// Create a new CreateOptions object. The rootfs is a
+    /// directory unless another backing store is chosen.
+    pub fn new() -> CreateOptions {
+        CreateOptions::default()
+    }
+
+    /// Backing store of the container's rootfs.
+    pub fn backing_store(mut self, store: BackingStore) -> CreateOptions {
+        self.backing_store = Some(store);
+        self
+    }
+
+    /// Filesystem created on block device backing stores
+    /// (lvm, loop, rbd).
+    pub fn fstype<S: Into<String>>(mut self, fstype: S) -> CreateOptions {
+        self.fstype = Some(fstype.into());
+        self
+    }
+
+    /// Size in bytes of block device backing stores.
+    pub fn fssize(mut self, size: u64) -> CreateOptions {
+        self.fssize = size;
+        self
+    }
+
+    /// ZFS dataset under which the container's dataset is created.
+    pub fn zfs_root<S: Into<String>>(mut self, root: S) -> CreateOptions {
+        self.zfs_root = Some(root.into());
+        self
+    }
+
+    /// LVM volume group in which the logical volume is created.
+    pub fn lvm_vg<S: Into<String>>(mut self, vg: S) -> CreateOptions {
+        self.lvm_vg = Some(vg.into());
+        self
+    }
+
+    /// Name of the LVM logical volume, the container's name
+    /// by default.
+    pub fn lvm_lv<S: Into<String>>(mut self, lv: S) -> CreateOptions {
+        self.lvm_lv = Some(lv.into());
+        self
+    }
+
+    /// LVM thin pool in which the logical volume is created.
+    pub fn lvm_thin_pool<S: Into<String>>(mut self, pool: S) -> CreateOptions {
+        self.lvm_thin_pool = Some(pool.into());
+        self
+    }
+
+    /// Ceph pool in which the RBD image is created.
+    pub fn rbd_pool<S: Into<String>>(mut self, pool: S) -> CreateOptions {
+        self.rbd_pool = Some(pool.into());
+        self
+    }
+
+    /// Name of the RBD image, the container's name by default.
+    pub fn rbd_name<S: Into<String>>(mut self, name: S) -> CreateOptions {
+        self.rbd_name = Some(name.into());
+        self
+    }
+
+    /// Path of the rootfs directory, for the dir backing store.
+    pub fn dir<S: Into<String>>(mut self, dir: S) -> CreateOptions {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Wether the template's output is hidden (`LXC_CREATE_QUIET`).
+    /// Otherwise it is written to the process' stdout and stderr.
+    /// Enabled by default.
+    pub fn quiet(mut self, quiet: bool) -> CreateOptions {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Call the given function with each line the template writes
+    /// to its stdout or stderr, e.g. to log why a template failed
+    /// or to display its progress.
+    ///
+    /// liblxc runs the template with the process' own stdout and
+    /// stderr, so these are redirected while the container is
+    /// created: output from other threads is captured as well. The
+    /// function must not write to stdout or stderr, e.g. through a
+    /// logger printing there, as its output would be fed back to it.
+    pub fn on_output<F>(mut self, func: F) -> CreateOptions
+        where F: Fn(&str) + Send + Sync + 'static
+    {
+        self.output = Some(OutputHandler(Arc::new(func)));
+        self
+    }
+
+    /// Append the template's output to the given buffer, one line
+    /// at a time. See `on_output`.
+    pub fn output_buffer(self, buffer: Arc<Mutex<String>>) -> CreateOptions {
+        self.on_output(move |line| {
+            let mut buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+            buffer.push_str(line);
+            buffer.push('\n');
+        })
+    }
+
+    /// Run the given function, capturing the template's output if
+    /// requested.
+    pub(crate) fn run<F, R>(&self, func: F) -> Result<R>
+        where F: FnOnce() -> R
+    {
+        match self.output {
+            Some(ref handler) => capture_output(handler, func),
+            None => Ok(func())
+        }
+    }
+
+    /// Get liblxc's create flags.
+    pub(crate) fn flags(&self) -> c_int {
+        if self.quiet && self.output.is_none() {
+            lib::LXC_CREATE_QUIET as c_int
+        }
+        else {
+            0
+        }
+    }
+
+    /// Build the backing store type and liblxc's bdev_specs struct.
+    /// The returned strings must outlive both.
+    pub(crate) fn to_raw(&self) -> (*const c_char, lib::bdev_specs, Vec<CString>) {
+        fn c_string(s: &str) -> CString {
+            CString::new(s).unwrap()
+        }
+
+        let bdevtype = self.backing_store.map(|b| c_string(b.as_str()));
+        let fstype = self.fstype.as_deref().map(c_string);
+        let zfs_root = self.zfs_root.as_deref().map(c_string);
+        let lvm_vg = self.lvm_vg.as_deref().map(c_string);
+        let lvm_lv = self.lvm_lv.as_deref().map(c_string);
+        let lvm_thin_pool = self.lvm_thin_pool.as_deref().map(c_string);
+        let rbd_pool = self.rbd_pool.as_deref().map(c_string);
+        let rbd_name = self.rbd_name.as_deref().map(c_string);
+        let dir = self.dir.as_deref().map(c_string);
+
+        let as_ptr = |s: &Option<CString>| {
+            s.as_ref().map_or(ptr::null_mut(), |s| s.as_ptr() as *mut c_char)
+        };
+
+        let specs = lib::bdev_specs {
+            fstype: as_ptr(&fstype),
+            fssize: self.fssize,
+            zfs: lib::bdev_specs__bindgen_ty_1 {
+                zfsroot: as_ptr(&zfs_root)
+            },
+            lvm: lib::bdev_specs__bindgen_ty_2 {
+                vg: as_ptr(&lvm_vg),
+                lv: as_ptr(&lvm_lv),
+                thinpool: as_ptr(&lvm_thin_pool)
+            },
+            dir: as_ptr(&dir),
+            rbd: lib::bdev_specs__bindgen_ty_3 {
+                rbdname: as_ptr(&rbd_name),
+                rbdpool: as_ptr(&rbd_pool)
+            }
+        };
+
+        let bdevtype_ptr = as_ptr(&bdevtype) as *const c_char;
+
+        // Moving the CStrings does not move their heap buffers
+        let strings = bdevtype.into_iter()
+            .chain(fstype)
+            .chain(zfs_root)
+            .chain(lvm_vg)
+            .chain(lvm_lv)
+            .chain(lvm_thin_pool)
+            .chain(rbd_pool)
+            .chain(rbd_name)
+            .chain(dir)
+            .collect();
+
+        (bdevtype_ptr, specs, strings)
+    }
+}
+
+/// Redirection of the process' stdout and stderr to a pipe, undone
+/// when dropped, even if the redirected function panics.
+struct Redirection {
+    saved_stdout: c_int,
+    saved_stderr: c_int,
+    stop: Option<File>,
+    reader: Option<JoinHandle<()>>
+}
+
+impl Drop for Redirection {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dup2(self.saved_stdout, 1);
+            libc::dup2(self.saved_stderr, 2);
+            libc::close(self.saved_stdout);
+            libc::close(self.saved_stderr);
+        }
+
+        // Closing the stop pipe makes the reader return once the
+        // pipe is drained, even if a process left by the template
+        // still holds a write end
+        self.stop.take();
+
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// Hand the lines read from the output pipe to the handler, until
+/// every write end is closed or, once the stop pipe is closed, until
+/// the output pipe is empty.
+fn forward_lines(mut output: File, stop: File, handler: &(dyn Fn(&str) + Send + Sync)) {
+    let mut buf = [0u8; 4096];
+    let mut line = Vec::new();
+    let mut stopping = false;
+
+    loop {
+        if !stopping {
+            let mut pfds = [
+                libc::pollfd { fd: output.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+                libc::pollfd { fd: stop.as_raw_fd(), events: libc::POLLIN, revents: 0 }
+            ];
+
+            if unsafe { libc::poll(pfds.as_mut_ptr(), 2, -1) } < 0 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                break;
+            }
+
+            if pfds[1].revents != 0 {
+                stopping = true;
+                unsafe { libc::fcntl(output.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK) };
+            } else if pfds[0].revents == 0 {
+                continue;
+            }
+        }
+
+        match output.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => line.extend_from_slice(&buf[..n]),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break
+        }
+
+        while let Some(pos) = line.iter().position(|b| *b == b'\n') {
+            let rest = line.split_off(pos + 1);
+            line.pop();
+            handler(&String::from_utf8_lossy(&line));
+            line = rest;
+        }
+    }
+
+    if !line.is_empty() {
+        handler(&String::from_utf8_lossy(&line));
+    }
+}
+
+/// Create a pipe whose ends are closed on exec.
+fn pipe() -> Result<(File, File)> {
+    let mut fds = [0 as c_int; 2];
+
+    unsafe {
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) < 0 {
+            return Err(Error::OperationFailed);
+        }
+
+        Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])))
+    }
+}
+
+/// Run the given function with the process' stdout and stderr
+/// redirected to a pipe, whose lines are handed to the handler.
+fn capture_output<F, R>(handler: &OutputHandler, func: F) -> Result<R>
+    where F: FnOnce() -> R
+{
+    let _lock = OUTPUT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+
+    let (output, output_write) = pipe()?;
+    let (stop_read, stop) = pipe()?;
+
+    unsafe {
+        let saved_stdout = libc::fcntl(1, libc::F_DUPFD_CLOEXEC, 0);
+        let saved_stderr = libc::fcntl(2, libc::F_DUPFD_CLOEXEC, 0);
+
+        if saved_stdout < 0 || saved_stderr < 0 {
+            for fd in [saved_stdout, saved_stderr].iter().filter(|fd| **fd >= 0) {
+                libc::close(*fd);
+            }
+
+            return Err(Error::OperationFailed);
+        }
+
+        let mut redirection = Redirection {
+            saved_stdout,
+            saved_stderr,
+            stop: Some(stop),
+            reader: None
+        };
+
+        // The template inherits the write end as its stdout and stderr,
+        // ours is closed when output_write is dropped
+        libc::dup2(output_write.as_raw_fd(), 1);
+        libc::dup2(output_write.as_raw_fd(), 2);
+        drop(output_write);
+
+        let handler = handler.0.clone();
+
+        redirection.reader = Some(std::thread::spawn(move || {
+            forward_lines(output, stop_read, &*handler);
+        }));
+
+        Ok(func())
+    }
+}
@@ -0,0 +1,150 @@
+//! Scripts run by liblxc at the various stages of a container's
+//! lifecycle (`lxc.hook.*`).
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Error, Result};
+
+/// Configuration key selecting how hooks receive their arguments,
+/// introduced in liblxc 3.0.
+#[cfg(feature = "v3_0")]
+pub(crate) const HOOK_VERSION_KEY: &str = "lxc.hook.version";
+
+/// Stage of a container's lifecycle at which a hook is run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hook {
+    /// Run in the host's namespaces, before the container's
+    /// namespaces are set up.
+    PreStart,
+
+    /// Run in the container's mount namespace, before the rootfs
+    /// is mounted.
+    PreMount,
+
+    /// Run in the container's namespaces, after the filesystems are
+    /// mounted but before the rootfs is pivoted to.
+    Mount,
+
+    /// Run after the filesystems are mounted and `/dev` is populated,
+    /// when `lxc.autodev` is set.
+    Autodev,
+
+    /// Run in the host's namespaces, after the container is set up
+    /// and just before its init is started.
+    StartHost,
+
+    /// Run in the container, right before its init is executed.
+    Start,
+
+    /// Run in the host's namespaces, with references to the
+    /// container's namespaces, after the container is shut down.
+    Stop,
+
+    /// Run in the host's namespaces, after the container is
+    /// shut down.
+    PostStop,
+
+    /// Run when the container is cloned into a new one.
+    Clone,
+
+    /// Run when the container is destroyed.
+    Destroy
+}
+
+impl Hook {
+    /// Name of the hook, as found in its configuration key.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Hook::PreStart => "pre-start",
+            Hook::PreMount => "pre-mount",
+            Hook::Mount => "mount",
+            Hook::Autodev => "autodev",
+            Hook::StartHost => "start-host",
+            Hook::Start => "start",
+            Hook::Stop => "stop",
+            Hook::PostStop => "post-stop",
+            Hook::Clone => "clone",
+            Hook::Destroy => "destroy"
+        }
+    }
+
+    /// Configuration key holding the scripts of the hook, such
+    /// as `lxc.hook.pre-start`.
+    pub fn key(&self) -> String {
+        format!("lxc.hook.{}", self.as_str())
+    }
+
+    /// Get all the hooks, in the order they are run.
+    pub fn all() -> &'static [Hook] {
+        &[
+            Hook::PreStart,
+            Hook::PreMount,
+            Hook::Mount,
+            Hook::Autodev,
+            Hook::StartHost,
+            Hook::Start,
+            Hook::Stop,
+            Hook::PostStop,
+            Hook::Clone,
+            Hook::Destroy
+        ]
+    }
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Hook {
+    type Err = Error;
+
+    /// Parse a hook name, with or without the `lxc.hook.` prefix.
+    fn from_str(s: &str) -> Result<Hook> {
+        let name = s.strip_prefix("lxc.hook.").unwrap_or(s);
+
+        Hook::all()
+            .iter()
+            .find(|hook| hook.as_str() == name)
+            .cloned()
+            .ok_or(Error::OperationFailed)
+    }
+}
+
+/// Way hook scripts receive information about the container
+/// (`lxc.hook.version`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookVersion {
+    /// Container name, section and hook type are passed as
+    /// command line arguments. This is the default.
+    Arguments,
+
+    /// Everything is passed through `LXC_*` environment variables,
+    /// such as `LXC_NAME` and `LXC_HOOK_TYPE`, leaving the command
+    /// line to the script's own arguments.
+    Environment
+}
+
+impl HookVersion {
+    /// Value of the version in `lxc.hook.version`.
+    #[cfg(feature = "v3_0")]
+    pub(crate) fn as_str(&self) -> &'static str {
+        match *self {
+            HookVersion::Arguments => "0",
+            HookVersion::Environment => "1"
+        }
+    }
+
+    /// Parse a `lxc.hook.version` value. An unset version is the
+    /// default one.
+    #[cfg(feature = "v3_0")]
+    pub(crate) fn parse(value: &str) -> Result<HookVersion> {
+        match value.trim() {
+            "" | "0" => Ok(HookVersion::Arguments),
+            "1" => Ok(HookVersion::Environment),
+            _ => Err(Error::OperationFailed)
+        }
+    }
+}
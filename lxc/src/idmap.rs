@@ -0,0 +1,244 @@
+//! User and group id mappings of unprivileged containers.
+
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use super::{Error, Result};
+
+/// Configuration key holding a container's id mappings. liblxc 2.1
+/// already understands it, and 3.0 removed the legacy `lxc.id_map`.
+pub(crate) const IDMAP_KEY: &str = "lxc.idmap";
+
+/// Kind of ids mapped by an IdMap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdKind {
+    /// User ids.
+    Uid,
+
+    /// Group ids.
+    Gid
+}
+
+impl IdKind {
+    /// Letter of the kind in `lxc.idmap` entries.
+    fn as_str(&self) -> &'static str {
+        match *self {
+            IdKind::Uid => "u",
+            IdKind::Gid => "g"
+        }
+    }
+
+    /// File listing the subordinate ids of each user.
+    fn subid_file(&self) -> &'static str {
+        match *self {
+            IdKind::Uid => "/etc/subuid",
+            IdKind::Gid => "/etc/subgid"
+        }
+    }
+}
+
+/// Mapping of a range of ids in the container to a range of
+/// ids on the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdMap {
+    /// Kind of the mapped ids.
+    pub kind: IdKind,
+
+    /// First id of the range in the container.
+    pub container_start: u32,
+
+    /// First id of the range on the host.
+    pub host_start: u32,
+
+    /// Number of ids in the range.
+    pub range: u32
+}
+
+impl IdMap {
+    /// Create a new IdMap object.
+    pub fn new(kind: IdKind, container_start: u32, host_start: u32, range: u32) -> IdMap {
+        IdMap {
+            kind,
+            container_start,
+            host_start,
+            range
+        }
+    }
+
+    /// Create a new user id mapping.
+    pub fn uid(container_start: u32, host_start: u32, range: u32) -> IdMap {
+        IdMap::new(IdKind::Uid, container_start, host_start, range)
+    }
+
+    /// Create a new group id mapping.
+    pub fn gid(container_start: u32, host_start: u32, range: u32) -> IdMap {
+        IdMap::new(IdKind::Gid, container_start, host_start, range)
+    }
+
+    /// Parse an `lxc.idmap` entry such as `u 0 100000 65536`.
+    pub fn parse(s: &str) -> Option<IdMap> {
+        let mut fields = s.split_whitespace();
+
+        let kind = match fields.next()? {
+            "u" => IdKind::Uid,
+            "g" => IdKind::Gid,
+            _ => return None
+        };
+
+        let container_start = fields.next()?.parse().ok()?;
+        let host_start = fields.next()?.parse().ok()?;
+        let range = fields.next()?.parse().ok()?;
+
+        if fields.next().is_some() {
+            return None;
+        }
+
+        Some(IdMap::new(kind, container_start, host_start, range))
+    }
+
+    /// Build the mappings of the subordinate ids of the given user
+    /// (name or numeric id), read from `/etc/subuid` or
+    /// `/etc/subgid`. The ranges are mapped one after the other,
+    /// starting from id 0 in the container.
+    pub fn from_subids(kind: IdKind, user: &str) -> Result<Vec<IdMap>> {
+        let content = fs::read_to_string(kind.subid_file()).map_err(|_| Error::OperationFailed)?;
+        let maps = IdMap::parse_subids(kind, &content, user);
+
+        if maps.is_empty() {
+            return Err(Error::OperationFailed);
+        }
+
+        Ok(maps)
+    }
+
+    /// Build the mappings of the subordinate ids of the given user
+    /// from the content of a `/etc/subuid` or `/etc/subgid` file.
+    pub fn parse_subids(kind: IdKind, content: &str, user: &str) -> Vec<IdMap> {
+        let mut maps = Vec::new();
+        let mut container_start = 0u32;
+
+        for line in content.lines() {
+            let mut fields = line.trim().split(':');
+
+            if fields.next() != Some(user) {
+                continue;
+            }
+
+            let start = fields.next().and_then(|f| f.parse().ok());
+            let count = fields.next().and_then(|f| f.parse().ok());
+
+            if let (Some(start), Some(count)) = (start, count) {
+                maps.push(IdMap::new(kind, container_start, start, count));
+                container_start = container_start.saturating_add(count);
+            }
+        }
+
+        maps
+    }
+
+    /// Check wether this mapping overlaps with another mapping of
+    /// the same kind, in the container or on the host.
+    pub fn overlaps(&self, other: &IdMap) -> bool {
+        let overlap = |a: u32, b: u32| {
+            u64::from(a) < u64::from(b) + u64::from(other.range)
+                && u64::from(b) < u64::from(a) + u64::from(self.range)
+        };
+
+        self.kind == other.kind
+            && (overlap(self.container_start, other.container_start)
+                || overlap(self.host_start, other.host_start))
+    }
+
+    /// Check that a set of mappings has no empty range, and that no
+    /// two mappings overlap.
+    pub fn validate(maps: &[IdMap]) -> Result<()> {
+        for (i, map) in maps.iter().enumerate() {
+            if map.range == 0 || maps[i + 1..].iter().any(|other| map.overlaps(other)) {
+                return Err(Error::OperationFailed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the host id of the given container id, if it is in
+    /// the range.
+    pub fn to_host(&self, id: u32) -> Option<u32> {
+        let offset = id.checked_sub(self.container_start)?;
+
+        if offset >= self.range {
+            return None;
+        }
+
+        self.host_start.checked_add(offset)
+    }
+
+    /// Check wether the given host id is in the range.
+    fn contains_host(&self, id: u32) -> bool {
+        id.checked_sub(self.host_start).is_some_and(|offset| offset < self.range)
+    }
+}
+
+impl std::fmt::Display for IdMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} {} {}", self.kind.as_str(), self.container_start, self.host_start, self.range)
+    }
+}
+
+/// Map an id of the given kind to the host. Ids that already are
+/// in a mapped host range are left as is.
+fn shift_id(maps: &[IdMap], kind: IdKind, id: u32) -> Result<u32> {
+    let mut maps = maps.iter().filter(|map| map.kind == kind);
+
+    if maps.clone().any(|map| map.contains_host(id)) {
+        return Ok(id);
+    }
+
+    maps.find_map(|map| map.to_host(id)).ok_or(Error::OperationFailed)
+}
+
+/// Change the ownership of every file under the given path, from
+/// container ids to the mapped host ids, so that an unprivileged
+/// container can use a rootfs extracted with the host's ids. Files
+/// already owned by mapped host ids are left untouched, so the shift
+/// can safely be run again.
+pub fn shift_ownership(path: &Path, maps: &[IdMap]) -> Result<()> {
+    IdMap::validate(maps)?;
+    shift_tree(path, maps)
+}
+
+/// Shift the ownership of a file and, for directories, of
+/// their content.
+fn shift_tree(path: &Path, maps: &[IdMap]) -> Result<()> {
+    let meta = fs::symlink_metadata(path).map_err(|_| Error::OperationFailed)?;
+
+    let uid = shift_id(maps, IdKind::Uid, meta.uid())?;
+    let gid = shift_id(maps, IdKind::Gid, meta.gid())?;
+
+    if uid != meta.uid() || gid != meta.gid() {
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+
+        unsafe {
+            if libc::lchown(c_path.as_ptr(), uid, gid) < 0 {
+                return Err(Error::OperationFailed);
+            }
+        }
+
+        // Changing the owner clears the setuid and setgid bits
+        if !meta.file_type().is_symlink() {
+            fs::set_permissions(path, meta.permissions()).map_err(|_| Error::OperationFailed)?;
+        }
+    }
+
+    if meta.is_dir() {
+        for entry in fs::read_dir(path).map_err(|_| Error::OperationFailed)? {
+            let entry = entry.map_err(|_| Error::OperationFailed)?;
+            shift_tree(&entry.path(), maps)?;
+        }
+    }
+
+    Ok(())
+}
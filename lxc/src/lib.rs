@@ -18,16 +18,65 @@
 //!     let containers = Container::list(lxcpath).unwrap();
 //!
 //!     for c in containers {
-//!         println!("{} - {}", c.name, c.state());
+//!         println!("{} - {}", c.name, c.state().unwrap());
 //!     }
 //! }
 //! ```
 
 extern crate libc;
 extern crate lxc_sys as lib;
+extern crate serde;
+extern crate serde_json;
 
 use libc::{c_char, c_void, c_int};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use std::ffi::{CStr, CString};
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::io::Read;
+use std::os::unix::io::FromRawFd;
+
+mod config;
+mod create;
+mod hook;
+mod idmap;
+mod migrate;
+mod monitor;
+mod retention;
+mod seccomp;
+mod security;
+mod snapshot;
+mod version;
+
+#[cfg(feature = "async")]
+mod asynchronous;
+
+#[cfg(feature = "v4_0")]
+mod notify;
+
+pub use config::GlobalConfig;
+pub use create::{BackingStore, CreateOptions, Rootfs};
+pub use hook::{Hook, HookVersion};
+pub use idmap::{shift_ownership, IdKind, IdMap};
+pub use migrate::{CriuFeatures, MigrateCommand, MigrateOptions};
+pub use monitor::{Event, Monitor, State};
+pub use retention::{PrunePlan, RetentionPolicy};
+pub use seccomp::{
+    SeccompAction, SeccompArch, SeccompArg, SeccompCompare, SeccompMode, SeccompPolicy, SeccompRule
+};
+pub use security::Capability;
+pub use snapshot::{Snapshot, SnapshotContainer};
+pub use version::Version;
+
+#[cfg(feature = "async")]
+pub use asynchronous::AsyncContainer;
+
+#[cfg(feature = "async")]
+pub use monitor::AsyncMonitor;
+
+#[cfg(feature = "v4_0")]
+pub use notify::{SeccompNotification, SeccompResponse, SeccompSupervisor};
 
 /// Custom error type for this library.
 #[derive(Debug)]
@@ -40,7 +89,18 @@ pub enum Error {
 
     /// The call to liblxc resulted in an error.
     // TODO: Better error handling of these cases
-    OperationFailed
+    OperationFailed,
+
+    /// The operation is not supported by the liblxc version
+    /// currently in use.
+    Unsupported,
+
+    /// liblxc could not be loaded.
+    LibraryNotFound,
+
+    /// The liblxc version in use is older than the one the
+    /// bindings were generated for.
+    IncompatibleVersion
 }
 
 impl std::fmt::Display for Error {
@@ -54,7 +114,10 @@ impl std::error::Error for Error {
         match *self {
             Error::ContainerDoesNotExists => "Container does not exist",
             Error::ContainerAlreadyExists => "Container already exists",
-            Error::OperationFailed => "Operation failed: the call to liblxc resulted in an error"
+            Error::OperationFailed => "Operation failed: the call to liblxc resulted in an error",
+            Error::Unsupported => "Operation not supported by the liblxc version in use",
+            Error::LibraryNotFound => "liblxc could not be loaded",
+            Error::IncompatibleVersion => "The liblxc version in use is not compatible with these bindings"
         }
     }
 }
@@ -62,6 +125,30 @@ impl std::error::Error for Error {
 /// Custom result type for this library.
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Initialize the library. When built with the `dlopen` feature,
+/// this loads liblxc, and should be called before any other function
+/// since they abort the process if liblxc cannot be loaded.
+/// In all cases, verifies that the liblxc in use is not older than
+/// the headers the bindings were generated from, point release
+/// included, as its container structure would then be smaller than
+/// expected.
+pub fn init() -> Result<()> {
+    #[cfg(feature = "dlopen")]
+    {
+        lib::load().map_err(|_| Error::LibraryNotFound)?;
+    }
+
+    let current = Version::current().ok_or(Error::IncompatibleVersion)?;
+
+    if let Some(required) = lib::HEADER_VERSION.and_then(Version::parse) {
+        if (current.major, current.minor, current.micro) < (required.major, required.minor, required.micro) {
+            return Err(Error::IncompatibleVersion);
+        }
+    }
+
+    Ok(())
+}
+
 /// Determine the version of LXC currently
 /// in use.
 pub fn version() -> &'static str {
@@ -71,6 +158,46 @@ pub fn version() -> &'static str {
     }
 }
 
+/// Check wether the liblxc in use provides the specified
+/// API extension.
+#[cfg(feature = "v3_0")]
+pub fn has_api_extension(name: &str) -> bool {
+    unsafe {
+        let name = CString::new(name).unwrap();
+        lib::lxc_has_api_extension(name.as_ptr())
+    }
+}
+
+/// Check wether the liblxc in use provides the specified
+/// API extension. API extensions were introduced in liblxc
+/// 3.0, so none are available when targeting older versions.
+#[cfg(not(feature = "v3_0"))]
+pub fn has_api_extension(_name: &str) -> bool {
+    false
+}
+
+/// Check wether the specified configuration key is
+/// supported by the liblxc in use.
+pub fn config_item_supported(key: &str) -> bool {
+    unsafe {
+        let key = CString::new(key).unwrap();
+        lib::lxc_config_item_is_supported(key.as_ptr())
+    }
+}
+
+/// Get a function from liblxc's container API, or an error
+/// if the liblxc in use does not provide it.
+fn supported<T>(func: Option<T>) -> Result<T> {
+    func.ok_or(Error::Unsupported)
+}
+
+/// Determine the default lxcpath configured on the system
+/// (`lxc.lxcpath`). For unprivileged users, liblxc resolves
+/// it to `~/.local/share/lxc` unless configured otherwise.
+pub fn default_lxcpath() -> Result<String> {
+    GlobalConfig::lxcpath().ok_or(Error::OperationFailed)
+}
+
 /// Represents an LXC template script used to build
 /// a container's rootfs.
 pub struct Template {
@@ -87,6 +214,12 @@ impl Template {
         }
     }
 
+    /// Create a Template object that runs no script, leaving
+    /// the container's rootfs empty.
+    pub fn none() -> Template {
+        Template::new("none")
+    }
+
     /// Add a parameter and its value that will
     /// be passed to the template script.
     pub fn option<S: Into<String>>(mut self, opt: S, value: S) -> Template {
@@ -96,48 +229,41 @@ impl Template {
     }
 }
 
-/// Represents an LXC container snapshot.
-pub struct Snapshot {
-    handle: lib::lxc_snapshot,
-
-    /// Name of the snapshot.
-    pub name: String,
-
-    /// Time the snapshot was created at.
-    pub created: String
-}
-
-impl Snapshot {
-    /// Create a Rust Snapshot object based on a
-    /// liblxc lxc_snapshot struct.
-    fn from_raw(raw: lib::lxc_snapshot) -> Snapshot {
-        unsafe {
-            Snapshot {
-                handle: raw,
-                name: CStr::from_ptr(raw.name as *const c_char).to_str().unwrap().to_string(),
-                created: CStr::from_ptr(raw.timestamp as *const c_char).to_str().unwrap().to_string(),
-            }
-        }
-    }
-}
+/// Signature shared by liblxc's container listing functions.
+type ListFn = unsafe extern "C" fn(
+    *const c_char,
+    *mut *mut *mut c_char,
+    *mut *mut *mut lib::lxc_container
+) -> c_int;
 
-impl Drop for Snapshot {
-    fn drop(&mut self) {
-        unsafe {
-            self.handle.free.unwrap()(&mut self.handle);
-        }
-    }
-}
+/// Signature of liblxc's functions returning a container's seccomp
+/// notify fd.
+#[cfg(feature = "v4_0")]
+type NotifyFdFn = unsafe extern "C" fn(*mut lib::lxc_container) -> c_int;
 
 /// Represents an LXC Container.
+///
+/// Container handles are reference counted by liblxc: cloning a
+/// Container gives another handle to the same underlying container,
+/// and handles can be shared between threads.
+///
+/// Queries (`state`, `is_running`, `get_config_item`, `get_keys`,
+/// `get_config_file_name`, `snapshot_list`) are protected by liblxc's
+/// own locks and may run concurrently. Operations that change the
+/// container's state, configuration or storage are not thread safe
+/// in liblxc, and are serialized between all the clones of a handle.
 #[derive(Debug)]
 pub struct Container {
     handle: *mut lib::lxc_container,
+    lock: Arc<Mutex<()>>,
 
     /// Name of the container.
     pub name: String,
 }
 
+unsafe impl Send for Container {}
+unsafe impl Sync for Container {}
+
 impl Container {
     /// Create a Rust container object based on
     /// liblxc's lxc_container C struct.
@@ -147,11 +273,18 @@ impl Container {
 
             Container {
                 handle: raw,
+                lock: Arc::new(Mutex::new(())),
                 name: CStr::from_ptr(c.name as *const c_char).to_str().unwrap().to_owned()
             }
         }
     }
 
+    /// Acquire the lock serializing the operations that liblxc does
+    /// not protect against concurrent use.
+    fn serialize(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Check wether the LXC container with the specified name is
     /// defined in the provided lxcpath.
     pub fn exists(lxcpath: &str, name: &str) -> bool {
@@ -160,28 +293,60 @@ impl Container {
             let name = CString::new(name).unwrap();
 
             let ct = lib::lxc_container_new(name.as_ptr(), lxcpath.as_ptr());
-            if ct == 0 as *mut lib::lxc_container {
+            if ct.is_null() {
                 return false;
             }
 
-            if (*ct).is_defined.unwrap()(ct) {
-                return true;
-            }
-
-            false
+            Container::from_raw(ct).is_defined().unwrap_or(false)
         }
     }
 
     /// Get a list of defined LXC containers in the specified
     /// lxcpath.
     pub fn list(lxcpath: &str) -> Result<Vec<Container>> {
+        Container::list_containers(lib::list_defined_containers, lxcpath)
+    }
+
+    /// Get a list of all the LXC containers in the specified lxcpath,
+    /// including running containers that are not defined.
+    pub fn list_all(lxcpath: &str) -> Result<Vec<Container>> {
+        Container::list_containers(lib::list_all_containers, lxcpath)
+    }
+
+    /// Get a list of the active LXC containers in the specified
+    /// lxcpath.
+    pub fn list_active(lxcpath: &str) -> Result<Vec<Container>> {
+        Container::list_containers(lib::list_active_containers, lxcpath)
+    }
+
+    /// Get the names of the defined LXC containers in the specified
+    /// lxcpath, without opening them.
+    pub fn list_names(lxcpath: &str) -> Result<Vec<String>> {
+        Container::list_container_names(lib::list_defined_containers, lxcpath)
+    }
+
+    /// Get the names of all the LXC containers in the specified
+    /// lxcpath, without opening them.
+    pub fn list_all_names(lxcpath: &str) -> Result<Vec<String>> {
+        Container::list_container_names(lib::list_all_containers, lxcpath)
+    }
+
+    /// Get the names of the active LXC containers in the specified
+    /// lxcpath, without opening them.
+    pub fn list_active_names(lxcpath: &str) -> Result<Vec<String>> {
+        Container::list_container_names(lib::list_active_containers, lxcpath)
+    }
+
+    /// Call one of liblxc's listing functions and build a Container
+    /// object for each returned container.
+    fn list_containers(func: ListFn, lxcpath: &str) -> Result<Vec<Container>> {
         unsafe {
             let lxcpath = CString::new(lxcpath).unwrap();
-            let mut conts = 0 as *mut *mut lib::lxc_container;
+            let mut conts = ptr::null_mut();
 
-            let count = lib::list_defined_containers(
+            let count = func(
                 lxcpath.as_ptr() as *const c_char,
-                0 as *mut *mut *mut c_char,
+                ptr::null_mut(),
                 &mut conts
             );
 
@@ -204,6 +369,39 @@ impl Container {
         }
     }
 
+    /// Call one of liblxc's listing functions and only retrieve the
+    /// names of the returned containers.
+    fn list_container_names(func: ListFn, lxcpath: &str) -> Result<Vec<String>> {
+        unsafe {
+            let lxcpath = CString::new(lxcpath).unwrap();
+            let mut names = ptr::null_mut();
+
+            let count = func(
+                lxcpath.as_ptr() as *const c_char,
+                &mut names,
+                ptr::null_mut()
+            );
+
+            if count < 0 {
+                return Err(Error::OperationFailed);
+            }
+            else if count == 0 {
+                return Ok(Vec::new())
+            }
+
+            let mut vec = Vec::with_capacity(count as usize);
+
+            for i in 0..count {
+                let elem = *names.offset(i as isize);
+                vec.push(CStr::from_ptr(elem).to_string_lossy().into_owned());
+                libc::free(elem as *mut c_void);
+            }
+
+            libc::free(names as *mut c_void);
+            Ok(vec)
+        }
+    }
+
     /// Get an LXC container by its name from the specified
     /// lxcpath.
     pub fn get(lxcpath: &str, name: &str) -> Result<Container> {
@@ -212,31 +410,77 @@ impl Container {
             let name = CString::new(name).unwrap();
 
             let ct = lib::lxc_container_new(name.as_ptr(), lxcpath.as_ptr());
-            if ct == 0 as *mut lib::lxc_container {
+            if ct.is_null() {
                 return Err(Error::OperationFailed);
             }
 
-            if !(*ct).is_defined.unwrap()(ct) {
+            // Released on error when dropped
+            let ct = Container::from_raw(ct);
+
+            if !ct.is_defined()? {
                 return Err(Error::ContainerDoesNotExists);
             }
 
-            Ok(Container::from_raw(ct))
+            Ok(ct)
         }
     }
 
+    /// Get an LXC container by its name from the system's
+    /// default lxcpath.
+    pub fn get_default(name: &str) -> Result<Container> {
+        Container::get(&default_lxcpath()?, name)
+    }
+
+    /// Get the names of all the LXC containers found in each of the
+    /// given lxcpaths, as `(lxcpath, name)` pairs.
+    pub fn list_names_in(lxcpaths: &[&str]) -> Result<Vec<(String, String)>> {
+        let mut vec = Vec::new();
+
+        for lxcpath in lxcpaths {
+            for name in Container::list_all_names(lxcpath)? {
+                vec.push((lxcpath.to_string(), name));
+            }
+        }
+
+        Ok(vec)
+    }
+
+    /// Create a new LXC container in the system's default
+    /// lxcpath.
+    pub fn create_default(name: &str, template: Template) -> Result<Container> {
+        Container::create(&default_lxcpath()?, name, template)
+    }
+
     /// Create a new LXC container.
     pub fn create(lxcpath: &str, name: &str, template: Template) -> Result<Container> {
+        Container::create_with(lxcpath, name, template, &CreateOptions::new())
+    }
+
+    /// Create a new LXC container with the specified backing store
+    /// and creation options.
+    pub fn create_with(lxcpath: &str, name: &str, template: Template, options: &CreateOptions) -> Result<Container> {
         unsafe {
             let lxcpath = CString::new(lxcpath).unwrap();
             let name = CString::new(name).unwrap();
             let template_name = CString::new(template.name.as_str()).unwrap();
 
+            // Like lxc-create, the "none" template creates an empty rootfs
+            let template_ptr = if template.name == "none" {
+                ptr::null()
+            }
+            else {
+                template_name.as_ptr()
+            };
+
             let ct = lib::lxc_container_new(name.as_ptr(), lxcpath.as_ptr());
-            if ct == 0 as *mut lib::lxc_container {
+            if ct.is_null() {
                 return Err(Error::OperationFailed);
             }
 
-            if (*ct).is_defined.unwrap()(ct) {
+            // Released on error when dropped
+            let ct = Container::from_raw(ct);
+
+            if ct.is_defined()? {
                 return Err(Error::ContainerAlreadyExists);
             }
 
@@ -252,32 +496,53 @@ impl Container {
                 .map(|s| s.as_ptr() as *const c_char)
                 .collect::<Vec<*const c_char>>();
 
-            ptr_template_opts.push(0 as *const c_char);
+            ptr_template_opts.push(ptr::null());
+
+            let (bdevtype, mut specs, _strings) = options.to_raw();
 
-            let ok = (*ct).create.unwrap()(
-                ct,
-                template_name.as_ptr(),
-                0 as *const c_char,
-                0 as *mut lib::bdev_specs,
-                lib::LXC_CREATE_QUIET as c_int,
+            let create = supported((*ct.handle).create)?;
+            let ok = options.run(|| create(
+                ct.handle,
+                template_ptr,
+                bdevtype,
+                &mut specs,
+                options.flags(),
                 ptr_template_opts.as_ptr()
-            );
+            ))?;
 
             if !ok {
                 return Err(Error::OperationFailed);
             }
 
-            Ok(Container::from_raw(ct))
+            Ok(ct)
         }
     }
 
+    /// Create a new LXC container from a rootfs built outside of LXC,
+    /// without running any template script. The container gets the
+    /// system's default configuration, and its rootfs must be a
+    /// directory (dir, btrfs or overlay backing stores).
+    pub fn create_from_rootfs(lxcpath: &str, name: &str, rootfs: &Rootfs, options: &CreateOptions) -> Result<Container> {
+        let ct = Container::create_with(lxcpath, name, Template::none(), options)?;
+
+        let populated = ct.get_config_item(create::ROOTFS_KEY)
+            .and_then(|path| rootfs.extract_to(&path));
+
+        if let Err(e) = populated {
+            let _ = ct.destroy();
+            return Err(e);
+        }
+
+        Ok(ct)
+    }
+
     /// Get the current configuration file name for the
     /// LXC container.
     pub fn get_config_file_name(&self) -> Result<String> {
         unsafe {
-            let ptr = (*self.handle).config_file_name.unwrap()(self.handle);
+            let ptr = supported((*self.handle).config_file_name)?(self.handle);
 
-            if ptr == 0 as *mut c_char {
+            if ptr.is_null() {
                 return Err(Error::OperationFailed);
             }
 
@@ -290,7 +555,7 @@ impl Container {
     pub fn get_keys(&self, key_prefix: &str) -> Result<Vec<String>> {
         unsafe {
             let key_prefix = CString::new(key_prefix).unwrap();
-            let length = (*self.handle).get_keys.unwrap()(self.handle, key_prefix.as_ptr(), 0 as *mut c_char, 0);
+            let length = supported((*self.handle).get_keys)?(self.handle, key_prefix.as_ptr(), ptr::null_mut(), 0);
             println!("pute {}", length);
 
             if length < 0 {
@@ -302,7 +567,7 @@ impl Container {
 
             let mut s = vec![0u8; length as usize];
 
-            let ok = (*self.handle).get_keys.unwrap()(self.handle, key_prefix.as_ptr(), s.as_mut_ptr() as *mut c_char, length);
+            let ok = supported((*self.handle).get_keys)?(self.handle, key_prefix.as_ptr(), s.as_mut_ptr() as *mut c_char, length);
             if ok < 0 {
                 return Err(Error::OperationFailed);
             }
@@ -319,7 +584,7 @@ impl Container {
     pub fn get_config_item(&self, key: &str) -> Result<String> {
         unsafe {
             let key = CString::new(key).unwrap();
-            let size = (*self.handle).get_config_item.unwrap()(self.handle, key.as_ptr(), 0 as *mut c_char, 0);
+            let size = supported((*self.handle).get_config_item)?(self.handle, key.as_ptr(), ptr::null_mut(), 0);
 
             if size < 0 {
                 return Err(Error::OperationFailed);
@@ -328,7 +593,7 @@ impl Container {
             // Allocate a string long enough to hold the returned value
             let mut value = vec![0u8; (size + 1) as usize];
 
-            let ok = (*self.handle).get_config_item.unwrap()(
+            let ok = supported((*self.handle).get_config_item)?(
                 self.handle,
                 key.as_ptr(),
                 value.as_mut_ptr() as *mut c_char,
@@ -349,11 +614,13 @@ impl Container {
     /// Set a key/value configuration option for an
     /// LXC container.
     pub fn set_config_item(&self, key: &str, value: &str) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
             let key = CString::new(key).unwrap();
             let value = CString::new(value).unwrap();
 
-            if !(*self.handle).set_config_item.unwrap()(self.handle, key.as_ptr(), value.as_ptr()) {
+            if !supported((*self.handle).set_config_item)?(self.handle, key.as_ptr(), value.as_ptr()) {
                 return Err(Error::OperationFailed);
             }
 
@@ -363,18 +630,22 @@ impl Container {
 
     /// Clear the container's in-memory configuration.
     pub fn clear_config(&self) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            (*self.handle).clear_config.unwrap()(self.handle);
+            supported((*self.handle).clear_config)?(self.handle);
             Ok(())
         }
     }
 
     /// Clear a specific container configuration item.
     pub fn clear_config_item(&self, key: &str) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
             let key = CString::new(key).unwrap();
 
-            if !(*self.handle).clear_config_item.unwrap()(self.handle, key.as_ptr()) {
+            if !supported((*self.handle).clear_config_item)?(self.handle, key.as_ptr()) {
                 return Err(Error::OperationFailed);
             }
 
@@ -382,12 +653,304 @@ impl Container {
         }
     }
 
+    /// Replace the container's id mappings (`lxc.idmap`), making it
+    /// an unprivileged container. The mappings must not overlap.
+    pub fn set_idmaps(&self, maps: &[IdMap]) -> Result<()> {
+        IdMap::validate(maps)?;
+        self.clear_config_item(idmap::IDMAP_KEY)?;
+
+        for map in maps {
+            self.set_config_item(idmap::IDMAP_KEY, &map.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the container's id mappings (`lxc.idmap`).
+    pub fn idmaps(&self) -> Result<Vec<IdMap>> {
+        let value = self.get_config_item(idmap::IDMAP_KEY)?;
+
+        value.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| IdMap::parse(line).ok_or(Error::OperationFailed))
+            .collect()
+    }
+
+    /// Shift the ownership of the container's rootfs into the range
+    /// of its id mappings, when not already done. The rootfs must
+    /// be a directory.
+    pub fn shift_rootfs(&self) -> Result<()> {
+        let maps = self.idmaps()?;
+        let rootfs = self.get_config_item(create::ROOTFS_KEY)?;
+
+        shift_ownership(std::path::Path::new(create::rootfs_dir(&rootfs)), &maps)
+    }
+
+    /// Set the AppArmor profile of the container (`lxc.apparmor.profile`),
+    /// e.g. `generated`, `unconfined` or a profile name.
+    pub fn set_apparmor_profile(&self, profile: &str) -> Result<()> {
+        self.set_config_item(security::APPARMOR_PROFILE_KEY, profile)
+    }
+
+    /// Get the AppArmor profile of the container.
+    pub fn apparmor_profile(&self) -> Result<String> {
+        self.get_config_item(security::APPARMOR_PROFILE_KEY)
+    }
+
+    /// Change wether the generated AppArmor profile allows nesting
+    /// containers (`lxc.apparmor.allow_nesting`).
+    #[cfg(feature = "v3_0")]
+    pub fn set_apparmor_allow_nesting(&self, allow: bool) -> Result<()> {
+        self.set_config_item(security::APPARMOR_ALLOW_NESTING_KEY, if allow { "1" } else { "0" })
+    }
+
+    /// Change wether the generated AppArmor profile allows nesting
+    /// containers. Not available before liblxc 3.0.
+    #[cfg(not(feature = "v3_0"))]
+    pub fn set_apparmor_allow_nesting(&self, _allow: bool) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Check wether the generated AppArmor profile allows nesting
+    /// containers.
+    #[cfg(feature = "v3_0")]
+    pub fn apparmor_allow_nesting(&self) -> Result<bool> {
+        security::parse_bool(&self.get_config_item(security::APPARMOR_ALLOW_NESTING_KEY)?)
+    }
+
+    /// Check wether the generated AppArmor profile allows nesting
+    /// containers. Not available before liblxc 3.0.
+    #[cfg(not(feature = "v3_0"))]
+    pub fn apparmor_allow_nesting(&self) -> Result<bool> {
+        Err(Error::Unsupported)
+    }
+
+    /// Set the SELinux context of the container
+    /// (`lxc.selinux.context`).
+    pub fn set_selinux_context(&self, context: &str) -> Result<()> {
+        self.set_config_item(security::SELINUX_CONTEXT_KEY, context)
+    }
+
+    /// Get the SELinux context of the container.
+    pub fn selinux_context(&self) -> Result<String> {
+        self.get_config_item(security::SELINUX_CONTEXT_KEY)
+    }
+
+    /// Replace the capabilities dropped when starting the container
+    /// (`lxc.cap.drop`). Returns an error if capabilities to keep
+    /// are configured, as liblxc does not allow mixing both, and
+    /// `Error::Unsupported` if the kernel does not know one of them.
+    pub fn set_cap_drop(&self, caps: &[Capability]) -> Result<()> {
+        security::check_caps(caps)?;
+
+        if !self.get_config_item(security::CAP_KEEP_KEY)?.trim().is_empty() {
+            return Err(Error::OperationFailed);
+        }
+
+        self.clear_config_item(security::CAP_DROP_KEY)?;
+
+        if caps.is_empty() {
+            return Ok(());
+        }
+
+        self.set_config_item(security::CAP_DROP_KEY, &security::format_caps(caps))
+    }
+
+    /// Get the capabilities dropped when starting the container.
+    pub fn cap_drop(&self) -> Result<Vec<Capability>> {
+        security::parse_caps(&self.get_config_item(security::CAP_DROP_KEY)?)
+    }
+
+    /// Replace the capabilities kept when starting the container,
+    /// all the others being dropped (`lxc.cap.keep`). An empty list
+    /// drops all the capabilities. Returns an error if capabilities
+    /// to drop are configured, as liblxc does not allow mixing both,
+    /// and `Error::Unsupported` if the kernel does not know one of them.
+    pub fn set_cap_keep(&self, caps: &[Capability]) -> Result<()> {
+        security::check_caps(caps)?;
+
+        if !self.get_config_item(security::CAP_DROP_KEY)?.trim().is_empty() {
+            return Err(Error::OperationFailed);
+        }
+
+        self.clear_config_item(security::CAP_KEEP_KEY)?;
+
+        if caps.is_empty() {
+            return self.set_config_item(security::CAP_KEEP_KEY, "none");
+        }
+
+        self.set_config_item(security::CAP_KEEP_KEY, &security::format_caps(caps))
+    }
+
+    /// Get the capabilities kept when starting the container.
+    pub fn cap_keep(&self) -> Result<Vec<Capability>> {
+        security::parse_caps(&self.get_config_item(security::CAP_KEEP_KEY)?)
+    }
+
+    /// Change wether the container's processes are prevented from
+    /// gaining privileges (`lxc.no_new_privs`).
+    pub fn set_no_new_privs(&self, no_new_privs: bool) -> Result<()> {
+        self.set_config_item(security::NO_NEW_PRIVS_KEY, if no_new_privs { "1" } else { "0" })
+    }
+
+    /// Check wether the container's processes are prevented from
+    /// gaining privileges.
+    pub fn no_new_privs(&self) -> Result<bool> {
+        security::parse_bool(&self.get_config_item(security::NO_NEW_PRIVS_KEY)?)
+    }
+
+    /// Set the path of the container's seccomp policy file
+    /// (`lxc.seccomp.profile`).
+    pub fn set_seccomp_profile(&self, path: &str) -> Result<()> {
+        self.set_config_item(seccomp::SECCOMP_PROFILE_KEY, path)
+    }
+
+    /// Get the path of the container's seccomp policy file.
+    pub fn seccomp_profile(&self) -> Result<String> {
+        self.get_config_item(seccomp::SECCOMP_PROFILE_KEY)
+    }
+
+    /// Write the seccomp policy to the given file, and use it as
+    /// the container's policy.
+    pub fn set_seccomp_policy(&self, policy: &SeccompPolicy, path: &str) -> Result<()> {
+        policy.write_to(path)?;
+        self.set_seccomp_profile(path)
+    }
+
+    /// Get a supervisor receiving the system calls that the running
+    /// container's seccomp policy forwards with the `notify` action.
+    /// The notify fd is requested from the container's monitor, so
+    /// this works for daemonized containers and from other processes.
+    /// liblxc only hands the notify fd out when no
+    /// `lxc.seccomp.notify.proxy` is configured.
+    #[cfg(feature = "v4_0")]
+    pub fn seccomp_supervisor(&self) -> Result<SeccompSupervisor> {
+        unsafe {
+            // The fd received from the monitor is ours, the one of the
+            // process that started the container belongs to its config
+            let (fd, owned) = match self.seccomp_notify_fd_active() {
+                Some(active) => (active(self.handle), true),
+                None => (supported((*self.handle).seccomp_notify_fd)?(self.handle), false)
+            };
+
+            if fd < 0 {
+                return Err(Error::OperationFailed);
+            }
+
+            let copy = libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0);
+
+            if owned {
+                libc::close(fd);
+            }
+
+            if copy < 0 {
+                return Err(Error::OperationFailed);
+            }
+
+            Ok(SeccompSupervisor::from_raw_fd(copy))
+        }
+    }
+
+    /// Get liblxc's `seccomp_notify_fd_active` function. The member was
+    /// added in a 4.0 point release, right after `seccomp_notify_fd`,
+    /// so it is only read when liblxc reports the API extension.
+    #[cfg(feature = "v4_0")]
+    unsafe fn seccomp_notify_fd_active(&self) -> Option<NotifyFdFn> {
+        if !has_api_extension("seccomp_notify_fd_active") {
+            return None;
+        }
+
+        let slot: *const Option<NotifyFdFn> = ptr::addr_of!((*self.handle).seccomp_notify_fd);
+        slot.add(1).read()
+    }
+
+    /// Forward the container's seccomp notifications to the proxy
+    /// listening at the given address, such as `unix:/run/proxy.sock`,
+    /// instead of handing them to a supervisor.
+    #[cfg(feature = "v4_0")]
+    pub fn set_seccomp_notify_proxy(&self, address: &str) -> Result<()> {
+        self.set_config_item(notify::SECCOMP_NOTIFY_PROXY_KEY, address)
+    }
+
+    /// Get the address of the container's seccomp notify proxy.
+    #[cfg(feature = "v4_0")]
+    pub fn seccomp_notify_proxy(&self) -> Result<String> {
+        self.get_config_item(notify::SECCOMP_NOTIFY_PROXY_KEY)
+    }
+
+    /// Set the cookie sent to the seccomp notify proxy along with
+    /// each notification, to identify the container.
+    #[cfg(feature = "v4_0")]
+    pub fn set_seccomp_notify_cookie(&self, cookie: &str) -> Result<()> {
+        self.set_config_item(notify::SECCOMP_NOTIFY_COOKIE_KEY, cookie)
+    }
+
+    /// Add a script to run at the given stage of the container's
+    /// lifecycle (`lxc.hook.*`). Scripts of the same hook are run in
+    /// the order they were added.
+    pub fn add_hook(&self, hook: Hook, path: &str) -> Result<()> {
+        self.set_config_item(&hook.key(), path)
+    }
+
+    /// Get the scripts of every hook of the container, in the order
+    /// they are run.
+    pub fn hooks(&self) -> Result<Vec<(Hook, String)>> {
+        let mut hooks = Vec::new();
+
+        for hook in Hook::all() {
+            let value = self.get_config_item(&hook.key())?;
+
+            hooks.extend(value.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| (*hook, line.to_string())));
+        }
+
+        Ok(hooks)
+    }
+
+    /// Remove every script of the given hook.
+    pub fn clear_hooks(&self, hook: Hook) -> Result<()> {
+        self.clear_config_item(&hook.key())
+    }
+
+    /// Set how the container's hook scripts receive their
+    /// arguments (`lxc.hook.version`).
+    #[cfg(feature = "v3_0")]
+    pub fn set_hook_version(&self, version: HookVersion) -> Result<()> {
+        self.set_config_item(hook::HOOK_VERSION_KEY, version.as_str())
+    }
+
+    /// Set how the container's hook scripts receive their
+    /// arguments. Not available before liblxc 3.0, where hooks
+    /// always get them on the command line.
+    #[cfg(not(feature = "v3_0"))]
+    pub fn set_hook_version(&self, _version: HookVersion) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Get how the container's hook scripts receive their
+    /// arguments.
+    #[cfg(feature = "v3_0")]
+    pub fn hook_version(&self) -> Result<HookVersion> {
+        HookVersion::parse(&self.get_config_item(hook::HOOK_VERSION_KEY)?)
+    }
+
+    /// Get how the container's hook scripts receive their
+    /// arguments. Before liblxc 3.0, they always get them on
+    /// the command line.
+    #[cfg(not(feature = "v3_0"))]
+    pub fn hook_version(&self) -> Result<HookVersion> {
+        Ok(HookVersion::Arguments)
+    }
+
     /// Save the container configuration to a file.
     pub fn save_config(&self, file_path: &str) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
             let file_path = CString::new(file_path).unwrap();
 
-            if !(*self.handle).save_config.unwrap()(self.handle, file_path.as_ptr()) {
+            if !supported((*self.handle).save_config)?(self.handle, file_path.as_ptr()) {
                 return Err(Error::OperationFailed);
             }
 
@@ -398,8 +961,10 @@ impl Container {
     /// Change wether the container wants to run disconnected
     /// from the terminal.
     pub fn want_daemonize(&self, want_daemonize: bool) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).want_daemonize.unwrap()(self.handle, want_daemonize) {
+            if !supported((*self.handle).want_daemonize)?(self.handle, want_daemonize) {
                 return Err(Error::OperationFailed);
             }
 
@@ -410,8 +975,10 @@ impl Container {
     /// Change wether the container wishes all the file descriptors
     /// to be closed on startup.
     pub fn want_close_all_fds(&self, want_close_all_fds: bool) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).want_close_all_fds.unwrap()(self.handle, want_close_all_fds) {
+            if !supported((*self.handle).want_close_all_fds)?(self.handle, want_close_all_fds) {
                 return Err(Error::OperationFailed);
             }
 
@@ -421,8 +988,10 @@ impl Container {
 
     /// Start the LXC container.
     pub fn start(&self) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).start.unwrap()(self.handle, 0 as c_int, 0 as *const *const c_char) {
+            if !supported((*self.handle).start)?(self.handle, 0 as c_int, ptr::null()) {
                 return Err(Error::OperationFailed);
             }
 
@@ -432,8 +1001,10 @@ impl Container {
 
     /// Start the LXC container.
     pub fn stop(&self) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).stop.unwrap()(self.handle) {
+            if !supported((*self.handle).stop)?(self.handle) {
                 return Err(Error::OperationFailed);
             }
 
@@ -441,26 +1012,56 @@ impl Container {
         }
     }
 
+    /// Wait for the container to reach the given state (e.g.
+    /// `RUNNING` or `STOPPED`). Returns an error if the state was
+    /// not reached within the timeout, in seconds (-1 to wait
+    /// forever).
+    pub fn wait(&self, state: &str, timeout: i32) -> Result<()> {
+        unsafe {
+            let state = CString::new(state).unwrap();
+
+            if !supported((*self.handle).wait)?(self.handle, state.as_ptr(), timeout) {
+                return Err(Error::OperationFailed);
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Check wether the container is defined, that is wether its
+    /// configuration exists.
+    pub fn is_defined(&self) -> Result<bool> {
+        unsafe {
+            Ok(supported((*self.handle).is_defined)?(self.handle))
+        }
+    }
+
     /// Check wether a container is running or not.
-    pub fn is_running(&self) -> bool {
+    pub fn is_running(&self) -> Result<bool> {
         unsafe {
-            (*self.handle).is_running.unwrap()(self.handle)
+            Ok(supported((*self.handle).is_running)?(self.handle))
         }
     }
 
     /// Determine the state of a container. Returns an upper-case
     /// word representing the state.
-    pub fn state(&self) -> &'static str {
+    pub fn state(&self) -> Result<&'static str> {
         unsafe {
-            let s = (*self.handle).state.unwrap()(self.handle);
-            CStr::from_ptr(s).to_str().unwrap()
+            let s = supported((*self.handle).state)?(self.handle);
+            if s.is_null() {
+                return Err(Error::OperationFailed);
+            }
+
+            CStr::from_ptr(s).to_str().map_err(|_| Error::OperationFailed)
         }
     }
 
     /// Freeze a running LXC container.
     pub fn freeze(&self) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).freeze.unwrap()(self.handle) {
+            if !supported((*self.handle).freeze)?(self.handle) {
                 return Err(Error::OperationFailed);
             }
 
@@ -470,8 +1071,10 @@ impl Container {
 
     /// Thaw a frozen LXC container.
     pub fn unfreeze(&self) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).unfreeze.unwrap()(self.handle) {
+            if !supported((*self.handle).unfreeze)?(self.handle) {
                 return Err(Error::OperationFailed);
             }
 
@@ -479,19 +1082,56 @@ impl Container {
         }
     }
 
+    /// Copy the container into a new container with the given
+    /// name, in the specified lxcpath.
+    pub fn clone_to(&self, name: &str, lxcpath: &str) -> Result<Container> {
+        self.clone_with(name, lxcpath, None, 0)
+    }
+
+    /// Clone the container with the given liblxc clone flags, on
+    /// the specified backing store or the container's own.
+    pub(crate) fn clone_with(&self, name: &str, lxcpath: &str, bdev: Option<BackingStore>, flags: u32) -> Result<Container> {
+        let _lock = self.serialize();
+
+        unsafe {
+            let name = CString::new(name).unwrap();
+            let lxcpath = CString::new(lxcpath).unwrap();
+            let bdevtype = bdev.map(|b| CString::new(b.as_str()).unwrap());
+
+            let ct = supported((*self.handle).clone)?(
+                self.handle,
+                name.as_ptr(),
+                lxcpath.as_ptr(),
+                flags as c_int,
+                bdevtype.as_ref().map_or(ptr::null(), |b| b.as_ptr()),
+                ptr::null(),
+                0,
+                ptr::null_mut()
+            );
+
+            if ct.is_null() {
+                return Err(Error::OperationFailed);
+            }
+
+            Ok(Container::from_raw(ct))
+        }
+    }
+
     /// Create a LXC container snapshot with the given path
     /// to the snapshot's comment file. Returns the
     /// zero-based snapshot number.
     pub fn snapshot(&self, comment_file: Option<&str>) -> Result<u32> {
+        let _lock = self.serialize();
+
         unsafe {
             let num: i32;
 
             if let Some(comment_file) = comment_file {
                 let comment_file = CString::new(comment_file).unwrap();
-                num = (*self.handle).snapshot.unwrap()(self.handle, comment_file.as_ptr());
+                num = supported((*self.handle).snapshot)?(self.handle, comment_file.as_ptr());
             }
             else {
-                num = (*self.handle).snapshot.unwrap()(self.handle, 0 as *const c_char);
+                num = supported((*self.handle).snapshot)?(self.handle, ptr::null());
             }
 
             if num < 0 {
@@ -502,36 +1142,99 @@ impl Container {
         }
     }
 
+    /// Create a LXC container snapshot with the given comment.
+    /// Returns the name of the snapshot.
+    pub fn snapshot_with_comment(&self, comment: &str) -> Result<String> {
+        self.snapshot_with_tags(Some(comment), &[])
+    }
+
+    /// Create a LXC container snapshot with an optional comment and
+    /// the given tags, stored in the snapshot's directory. Returns
+    /// the name of the snapshot, or an error if a tag spans several
+    /// lines.
+    pub fn snapshot_with_tags(&self, comment: Option<&str>, tags: &[&str]) -> Result<String> {
+        // Tags are stored one per line
+        if tags.iter().any(|tag| tag.contains(['\n', '\r'])) {
+            return Err(Error::OperationFailed);
+        }
+
+        // liblxc copies the comment from a file, written in a private
+        // directory so that it cannot be swapped for a symlink
+        let num = match comment {
+            Some(comment) => {
+                let dir = snapshot::CommentDir::new()?;
+                let path = dir.write(comment)?;
+
+                self.snapshot(Some(&path))
+            },
+            None => self.snapshot(None)
+        };
+
+        let name = format!("snap{}", num?);
+
+        if !tags.is_empty() {
+            let snap = self.snapshot_list()?
+                .into_iter()
+                .find(|snap| snap.name == name)
+                .ok_or(Error::OperationFailed)?;
+
+            let mut content = tags.join("\n");
+            content.push('\n');
+
+            std::fs::write(format!("{}/{}/{}", snap.lxcpath, name, snapshot::TAGS_FILE), content)
+                .map_err(|_| Error::OperationFailed)?;
+        }
+
+        Ok(name)
+    }
+
     /// Obtain a list of container snapshot.
     pub fn snapshot_list(&self) -> Result<Vec<Snapshot>> {
         unsafe {
-            let mut ptr = 0 as *mut lib::lxc_snapshot;
-            let count = (*self.handle).snapshot_list.unwrap()(self.handle, &mut ptr);
+            let mut ptr = ptr::null_mut();
+            let count = supported((*self.handle).snapshot_list)?(self.handle, &mut ptr);
 
             if count < 0 {
                 return Err(Error::OperationFailed);
             }
 
-            let count = count as usize;
-            let vec: Vec<lib::lxc_snapshot> = Vec::from_raw_parts(ptr, count, count);
+            Ok(snapshot::snapshots_from_raw(ptr, count as usize))
+        }
+    }
 
-            let vec = vec.into_iter()
-                .map(|s| Snapshot::from_raw(s))
-                .collect::<Vec<Snapshot>>();
+    /// Apply a retention policy to the container's snapshots,
+    /// destroying the ones it does not keep. With `dry_run`, only
+    /// returns the plan.
+    pub fn snapshot_prune(&self, policy: &RetentionPolicy, dry_run: bool) -> Result<PrunePlan> {
+        let plan = policy.plan(&self.snapshot_list()?);
 
-            Ok(vec)
+        if !dry_run {
+            for name in plan.prune.iter() {
+                self.snapshot_destroy(name)?;
+            }
         }
+
+        Ok(plan)
+    }
+
+    /// Take a snapshot of the container, then apply the retention
+    /// policy. Meant to be called periodically, e.g. every hour.
+    pub fn snapshot_and_prune(&self, policy: &RetentionPolicy) -> Result<PrunePlan> {
+        self.snapshot(None)?;
+        self.snapshot_prune(policy, false)
     }
 
     /// Restore the specified snapshot as a new container with the
     /// given name. If the given name if identical to the original
     /// container's name, it will be reaplced.
     pub fn snapshot_restore(&self, snap_name: &str, container_name: &str) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
             let snap_name = CString::new(snap_name).unwrap();
             let container_name = CString::new(container_name).unwrap();
 
-            if !(*self.handle).snapshot_restore.unwrap()(self.handle, snap_name.as_ptr(), container_name.as_ptr()) {
+            if !supported((*self.handle).snapshot_restore)?(self.handle, snap_name.as_ptr(), container_name.as_ptr()) {
                 return Err(Error::OperationFailed);
             }
 
@@ -541,10 +1244,12 @@ impl Container {
 
     /// Destroy the specified snapshot.
     pub fn snapshot_destroy(&self, snap_name: &str) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
             let snap_name = CString::new(snap_name).unwrap();
 
-            if !(*self.handle).snapshot_destroy.unwrap()(self.handle, snap_name.as_ptr()) {
+            if !supported((*self.handle).snapshot_destroy)?(self.handle, snap_name.as_ptr()) {
                 return Err(Error::OperationFailed);
             }
 
@@ -554,8 +1259,10 @@ impl Container {
 
     /// Destroy all the container's snapshots.
     pub fn snapshot_destroy_all(&self) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).snapshot_destroy_all.unwrap()(self.handle) {
+            if !supported((*self.handle).snapshot_destroy_all)?(self.handle) {
                 return Err(Error::OperationFailed);
             }
 
@@ -567,10 +1274,12 @@ impl Container {
     /// specified directory. There is the possibility to stop the
     /// container after the ckeckpoint is done.
     pub fn checkpoint(&self, directory: &str, stop: bool, verbose: bool) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
             let directory = CString::new(directory).unwrap();
 
-            if !(*self.handle).checkpoint.unwrap()(self.handle, directory.as_ptr() as *mut c_char, stop, verbose) {
+            if !supported((*self.handle).checkpoint)?(self.handle, directory.as_ptr() as *mut c_char, stop, verbose) {
                 return Err(Error::OperationFailed);
             }
 
@@ -581,10 +1290,12 @@ impl Container {
     /// Restore a container from a checkpoint previously dumped into
     /// the specified directory.
     pub fn restore(&self, directory: &str, verbose: bool) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
             let directory = CString::new(directory).unwrap();
 
-            if !(*self.handle).restore.unwrap()(self.handle, directory.as_ptr() as *mut c_char, verbose) {
+            if !supported((*self.handle).restore)?(self.handle, directory.as_ptr() as *mut c_char, verbose) {
                 return Err(Error::OperationFailed);
             }
 
@@ -592,12 +1303,58 @@ impl Container {
         }
     }
 
+    /// Checkpoint, pre-dump or restore an LXC container with CRIU,
+    /// or check the features supported by CRIU. Repeated pre-dumps
+    /// followed by a dump allow iterative pre-copy migration.
+    pub fn migrate(&self, cmd: MigrateCommand, options: &MigrateOptions) -> Result<()> {
+        let _lock = self.serialize();
+
+        unsafe {
+            let (mut opts, _strings) = options.to_raw();
+
+            let ret = supported((*self.handle).migrate)?(
+                self.handle,
+                cmd.as_raw(),
+                &mut opts,
+                std::mem::size_of::<lib::migrate_opts>() as libc::c_uint
+            );
+
+            if ret != 0 {
+                return Err(Error::OperationFailed);
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Determine which of the requested CRIU features are supported
+    /// on the host, before checkpointing the container.
+    pub fn criu_features(&self, requested: CriuFeatures) -> Result<CriuFeatures> {
+        let mut available = CriuFeatures::empty();
+
+        // liblxc stops at the first unsupported feature, so each
+        // one is checked on its own
+        for feature in requested.iter() {
+            let options = MigrateOptions::new("").features_to_check(feature);
+
+            match self.migrate(MigrateCommand::FeatureCheck, &options) {
+                Ok(()) => available |= feature,
+                Err(Error::OperationFailed) => {},
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(available)
+    }
+
     /// Request the container to shutdown. Returns an error
     /// if the container failed to shutdown in the
     /// given time.
     pub fn shutdown(&self, timeout: i32) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).shutdown.unwrap()(self.handle, timeout) {
+            if !supported((*self.handle).shutdown)?(self.handle, timeout) {
                 return Err(Error::OperationFailed);
             }
 
@@ -605,10 +1362,84 @@ impl Container {
         }
     }
 
+    /// Run a Rust closure inside the namespaces the container was
+    /// created with, entered through the container's init process.
+    /// The closure runs in a forked child, its result is serialized
+    /// and sent back to the caller over a pipe.
+    pub fn run_in<F, R>(&self, func: F) -> Result<R>
+        where F: FnOnce() -> R, R: Serialize + DeserializeOwned
+    {
+        let _lock = self.serialize();
+
+        unsafe {
+            let mut fds = [0 as c_int; 2];
+            if libc::pipe(fds.as_mut_ptr()) < 0 {
+                return Err(Error::OperationFailed);
+            }
+
+            let mut payload = RunInPayload {
+                func: Some(func),
+                fd: fds[1]
+            };
+
+            let mut options: lib::lxc_attach_options_t = std::mem::zeroed();
+            options.attach_flags = lib::LXC_ATTACH_DEFAULT as c_int;
+            // Let liblxc enter the namespaces the container was
+            // actually created with
+            options.namespaces = -1;
+            options.personality = -1;
+            options.uid = -1i32 as libc::uid_t;
+            options.gid = -1i32 as libc::gid_t;
+            options.env_policy = lib::lxc_attach_env_policy_t::LXC_ATTACH_KEEP_ENV;
+            options.stdin_fd = 0;
+            options.stdout_fd = 1;
+            options.stderr_fd = 2;
+
+            // Like LXC_ATTACH_OPTIONS_DEFAULT, do not log to any fd
+            #[cfg(feature = "v3_0")]
+            {
+                options.log_fd = -libc::EBADF;
+            }
+
+            let mut pid: libc::pid_t = 0;
+            let ret = supported((*self.handle).attach)?(
+                self.handle,
+                Some(run_in_trampoline::<F, R>),
+                &mut payload as *mut RunInPayload<F> as *mut c_void,
+                &mut options,
+                &mut pid
+            );
+
+            // Only the child writes to the pipe
+            libc::close(fds[1]);
+
+            if ret < 0 {
+                libc::close(fds[0]);
+                return Err(Error::OperationFailed);
+            }
+
+            let mut output = Vec::new();
+            let read = std::fs::File::from_raw_fd(fds[0]).read_to_end(&mut output);
+
+            let mut status: c_int = 0;
+            if libc::waitpid(pid, &mut status, 0) < 0 {
+                return Err(Error::OperationFailed);
+            }
+
+            if read.is_err() || !libc::WIFEXITED(status) || libc::WEXITSTATUS(status) != 0 {
+                return Err(Error::OperationFailed);
+            }
+
+            serde_json::from_slice(&output).map_err(|_| Error::OperationFailed)
+        }
+    }
+
     /// Destroy the LXC container and all its snapshots.
     pub fn destroy_with_snapshots(self) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).destroy_with_snapshots.unwrap()(self.handle) {
+            if !supported((*self.handle).destroy_with_snapshots)?(self.handle) {
                 return Err(Error::OperationFailed);
             }
 
@@ -618,8 +1449,10 @@ impl Container {
 
     /// Destroy the LXC container.
     pub fn destroy(self) -> Result<()> {
+        let _lock = self.serialize();
+
         unsafe {
-            if !(*self.handle).destroy.unwrap()(self.handle) {
+            if !supported((*self.handle).destroy)?(self.handle) {
                 return Err(Error::OperationFailed);
             }
 
@@ -628,6 +1461,73 @@ impl Container {
     }
 }
 
+/// Data handed to the attached child by `Container::run_in`.
+struct RunInPayload<F> {
+    func: Option<F>,
+    fd: c_int
+}
+
+/// Exec function called by liblxc in the attached child. Runs the
+/// closure and writes its serialized result to the pipe.
+extern "C" fn run_in_trampoline<F, R>(payload: *mut c_void) -> c_int
+    where F: FnOnce() -> R, R: Serialize
+{
+    unsafe {
+        let payload = &mut *(payload as *mut RunInPayload<F>);
+        let func = match payload.func.take() {
+            Some(func) => func,
+            None => return 1
+        };
+
+        // Never unwind across the FFI boundary
+        let result = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(func)) {
+            Ok(result) => result,
+            Err(_) => return 1
+        };
+
+        let data = match serde_json::to_vec(&result) {
+            Ok(data) => data,
+            Err(_) => return 1
+        };
+
+        let mut written = 0;
+        while written < data.len() {
+            let n = libc::write(
+                payload.fd,
+                data[written..].as_ptr() as *const c_void,
+                data.len() - written
+            );
+
+            if n < 0 {
+                return 1;
+            }
+
+            written += n as usize;
+        }
+
+        libc::close(payload.fd);
+        0
+    }
+}
+
+impl Clone for Container {
+    fn clone(&self) -> Container {
+        // Sharing the handle without a reference would lead to a use
+        // after free, and Clone cannot report errors
+        unsafe {
+            if lib::lxc_container_get(self.handle) != 1 {
+                panic!("failed to take a reference to container {}: it is being freed", self.name);
+            }
+        }
+
+        Container {
+            handle: self.handle,
+            lock: self.lock.clone(),
+            name: self.name.clone()
+        }
+    }
+}
+
 impl Drop for Container {
     fn drop(&mut self) {
         unsafe {
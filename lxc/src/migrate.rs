@@ -0,0 +1,231 @@
+//! Options of liblxc's `migrate` call, used to checkpoint and
+//! restore containers with CRIU.
+
+use crate::lib;
+use std::ffi::CString;
+use std::ops::{BitOr, BitOrAssign};
+use std::ptr;
+
+use libc::c_char;
+
+/// Operation performed by `Container::migrate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MigrateCommand {
+    /// Dump the container's memory without stopping it, so that
+    /// the final dump only has to transfer the pages modified since.
+    PreDump,
+
+    /// Checkpoint the container.
+    Dump,
+
+    /// Restore the container from a checkpoint.
+    Restore,
+
+    /// Check wether the host's CRIU supports the requested
+    /// features.
+    FeatureCheck
+}
+
+impl MigrateCommand {
+    /// Get liblxc's value of the command.
+    pub(crate) fn as_raw(self) -> libc::c_uint {
+        match self {
+            MigrateCommand::PreDump => lib::MIGRATE_PRE_DUMP,
+            MigrateCommand::Dump => lib::MIGRATE_DUMP,
+            MigrateCommand::Restore => lib::MIGRATE_RESTORE,
+            MigrateCommand::FeatureCheck => lib::MIGRATE_FEATURE_CHECK
+        }
+    }
+}
+
+/// Set of optional CRIU features, as checked by the
+/// `FeatureCheck` migrate command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CriuFeatures {
+    bits: u64
+}
+
+impl CriuFeatures {
+    /// Tracking of the memory pages modified since the last dump,
+    /// needed by pre-dumps.
+    pub const MEM_TRACK: CriuFeatures = CriuFeatures { bits: lib::FEATURE_MEM_TRACK as u64 };
+
+    /// Lazy migration of the memory pages, after the restore.
+    pub const LAZY_PAGES: CriuFeatures = CriuFeatures { bits: lib::FEATURE_LAZY_PAGES as u64 };
+
+    /// Get an empty set of features.
+    pub fn empty() -> CriuFeatures {
+        CriuFeatures { bits: 0 }
+    }
+
+    /// Get the set of all the features known to liblxc.
+    pub fn all() -> CriuFeatures {
+        CriuFeatures::MEM_TRACK | CriuFeatures::LAZY_PAGES
+    }
+
+    /// Get the raw `FEATURE_*` bitmask.
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+
+    /// Check wether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Check wether all the given features are in the set.
+    pub fn contains(&self, other: CriuFeatures) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    /// Iterate over each individual feature of the set.
+    pub(crate) fn iter(self) -> impl Iterator<Item = CriuFeatures> {
+        (0..64)
+            .map(|bit| CriuFeatures { bits: 1 << bit })
+            .filter(move |feature| self.contains(*feature))
+    }
+}
+
+impl BitOr for CriuFeatures {
+    type Output = CriuFeatures;
+
+    fn bitor(self, other: CriuFeatures) -> CriuFeatures {
+        CriuFeatures { bits: self.bits | other.bits }
+    }
+}
+
+impl BitOrAssign for CriuFeatures {
+    fn bitor_assign(&mut self, other: CriuFeatures) {
+        self.bits |= other.bits;
+    }
+}
+
+/// Options passed to CRIU when migrating a container.
+#[derive(Debug, Clone, Default)]
+pub struct MigrateOptions {
+    directory: String,
+    verbose: bool,
+    stop: bool,
+    predump_dir: Option<String>,
+    pageserver_address: Option<String>,
+    pageserver_port: Option<u16>,
+    preserves_inodes: bool,
+    action_script: Option<String>,
+    disable_skip_in_flight: bool,
+    ghost_limit: u64,
+    features_to_check: CriuFeatures
+}
+
+impl MigrateOptions {
+    /// Create a new MigrateOptions object, with the directory
+    /// where the checkpoint files are dumped or read from.
+    pub fn new<S: Into<String>>(directory: S) -> MigrateOptions {
+        MigrateOptions {
+            directory: directory.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Enable CRIU's verbose logging.
+    pub fn verbose(mut self, verbose: bool) -> MigrateOptions {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Stop the container after the dump.
+    pub fn stop(mut self, stop: bool) -> MigrateOptions {
+        self.stop = stop;
+        self
+    }
+
+    /// Directory of a previous pre-dump, relative to the dump
+    /// directory, to only dump the pages modified since.
+    pub fn predump_dir<S: Into<String>>(mut self, dir: S) -> MigrateOptions {
+        self.predump_dir = Some(dir.into());
+        self
+    }
+
+    /// Send the memory pages to a CRIU page server listening on
+    /// the given address and port instead of writing them to the
+    /// dump directory.
+    pub fn pageserver<S: Into<String>>(mut self, address: S, port: u16) -> MigrateOptions {
+        self.pageserver_address = Some(address.into());
+        self.pageserver_port = Some(port);
+        self
+    }
+
+    /// Wether the container's rootfs inodes are preserved on the
+    /// destination host, which lets CRIU skip dumping them.
+    pub fn preserves_inodes(mut self, preserves: bool) -> MigrateOptions {
+        self.preserves_inodes = preserves;
+        self
+    }
+
+    /// Script CRIU runs at the different steps of the migration.
+    pub fn action_script<S: Into<String>>(mut self, script: S) -> MigrateOptions {
+        self.action_script = Some(script.into());
+        self
+    }
+
+    /// Fail the dump if TCP connections are still in flight,
+    /// instead of skipping them.
+    pub fn disable_skip_in_flight(mut self, disable: bool) -> MigrateOptions {
+        self.disable_skip_in_flight = disable;
+        self
+    }
+
+    /// Maximum size, in bytes, of the deleted files CRIU dumps
+    /// along with the container (0 for CRIU's default).
+    pub fn ghost_limit(mut self, limit: u64) -> MigrateOptions {
+        self.ghost_limit = limit;
+        self
+    }
+
+    /// Features to check with the `FeatureCheck` command.
+    pub fn features_to_check(mut self, features: CriuFeatures) -> MigrateOptions {
+        self.features_to_check = features;
+        self
+    }
+
+    /// Build liblxc's migrate_opts struct. The returned strings
+    /// must outlive the struct.
+    pub(crate) fn to_raw(&self) -> (lib::migrate_opts, Vec<CString>) {
+        fn c_string(s: &str) -> CString {
+            CString::new(s).unwrap()
+        }
+
+        let directory = c_string(&self.directory);
+        let predump_dir = self.predump_dir.as_deref().map(c_string);
+        let pageserver_address = self.pageserver_address.as_deref().map(c_string);
+        let pageserver_port = self.pageserver_port.map(|p| c_string(&p.to_string()));
+        let action_script = self.action_script.as_deref().map(c_string);
+
+        let as_ptr = |s: &Option<CString>| {
+            s.as_ref().map_or(ptr::null_mut(), |s| s.as_ptr() as *mut c_char)
+        };
+
+        let opts = lib::migrate_opts {
+            directory: directory.as_ptr() as *mut c_char,
+            verbose: self.verbose,
+            stop: self.stop,
+            predump_dir: as_ptr(&predump_dir),
+            pageserver_address: as_ptr(&pageserver_address),
+            pageserver_port: as_ptr(&pageserver_port),
+            preserves_inodes: self.preserves_inodes,
+            action_script: as_ptr(&action_script),
+            disable_skip_in_flight: self.disable_skip_in_flight,
+            ghost_limit: self.ghost_limit,
+            features_to_check: self.features_to_check.bits()
+        };
+
+        // Moving the CStrings does not move their heap buffers
+        let strings = Some(directory).into_iter()
+            .chain(predump_dir)
+            .chain(pageserver_address)
+            .chain(pageserver_port)
+            .chain(action_script)
+            .collect();
+
+        (opts, strings)
+    }
+}
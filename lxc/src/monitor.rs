@@ -0,0 +1,390 @@
+//! Container lifecycle events, read from the LXC monitor socket.
+//!
+//! Containers report their state changes to `lxc-monitord`, which
+//! rebroadcasts them on an abstract unix socket per lxcpath. The
+//! daemon is spawned when no one is listening on that socket yet.
+
+use libc::c_int;
+use std::fmt;
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::CommandExt;
+use std::os::unix::net::UnixStream;
+use std::process::Command;
+use std::str::FromStr;
+
+use super::{Error, Result};
+
+/// Size of liblxc's `struct lxc_msg`: the message type, the
+/// container name (`NAME_MAX + 1` bytes), the value and the pid.
+pub(crate) const MSG_SIZE: usize = 4 + 256 + 4 + 4;
+
+/// Offset of the value in `struct lxc_msg`.
+const VALUE_OFFSET: usize = 4 + 256;
+
+/// Message types of `struct lxc_msg`.
+const MSG_STATE: i32 = 0;
+const MSG_EXIT_CODE: i32 = 2;
+
+/// Locations where distributions install `lxc-monitord`.
+const MONITORD_PATHS: &[&str] = &[
+    "/usr/libexec/lxc/lxc-monitord",
+    "/usr/lib/lxc/lxc-monitord",
+    "/usr/lib/x86_64-linux-gnu/lxc/lxc-monitord",
+    "/usr/lib/aarch64-linux-gnu/lxc/lxc-monitord",
+    "/usr/local/libexec/lxc/lxc-monitord",
+    "/usr/local/lib/lxc/lxc-monitord"
+];
+
+/// State of an LXC container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    Stopped,
+    Starting,
+    Running,
+    Stopping,
+    Aborting,
+    Freezing,
+    Frozen,
+    Thawed
+}
+
+impl State {
+    /// Get the State from liblxc's `lxc_state_t` value.
+    fn from_raw(value: i32) -> Option<State> {
+        match value {
+            0 => Some(State::Stopped),
+            1 => Some(State::Starting),
+            2 => Some(State::Running),
+            3 => Some(State::Stopping),
+            4 => Some(State::Aborting),
+            5 => Some(State::Freezing),
+            6 => Some(State::Frozen),
+            7 => Some(State::Thawed),
+            _ => None
+        }
+    }
+
+    /// Upper-case word representing the state, as used by liblxc.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            State::Stopped => "STOPPED",
+            State::Starting => "STARTING",
+            State::Running => "RUNNING",
+            State::Stopping => "STOPPING",
+            State::Aborting => "ABORTING",
+            State::Freezing => "FREEZING",
+            State::Frozen => "FROZEN",
+            State::Thawed => "THAWED"
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for State {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<State> {
+        (0..8)
+            .filter_map(State::from_raw)
+            .find(|state| state.as_str() == s)
+            .ok_or(Error::OperationFailed)
+    }
+}
+
+/// Lifecycle event of an LXC container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The container changed state.
+    State {
+        /// Name of the container.
+        name: String,
+
+        /// New state of the container.
+        state: State
+    },
+
+    /// The container's init process exited.
+    Exit {
+        /// Name of the container.
+        name: String,
+
+        /// Exit status of the init process, as returned by `waitpid`.
+        status: i32
+    }
+}
+
+impl Event {
+    /// Name of the container the event is about.
+    pub fn name(&self) -> &str {
+        match *self {
+            Event::State { ref name, .. } => name,
+            Event::Exit { ref name, .. } => name
+        }
+    }
+
+    /// Decode a `struct lxc_msg`. Returns `None` for the messages
+    /// that are not lifecycle events.
+    pub(crate) fn decode(msg: &[u8; MSG_SIZE]) -> Option<Event> {
+        let int = |offset: usize| {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&msg[offset..offset + 4]);
+            i32::from_ne_bytes(bytes)
+        };
+
+        let name = &msg[4..VALUE_OFFSET];
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        let name = String::from_utf8_lossy(&name[..len]).into_owned();
+
+        match int(0) {
+            MSG_STATE => State::from_raw(int(VALUE_OFFSET)).map(|state| Event::State { name, state }),
+            MSG_EXIT_CODE => Some(Event::Exit { name, status: int(VALUE_OFFSET) }),
+            _ => None
+        }
+    }
+}
+
+/// Compute the 64-bit FNV-1a hash used by liblxc to name the
+/// monitor socket.
+fn fnv_64a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Build the address of the monitor socket of the given lxcpath.
+fn socket_address(lxcpath: &str) -> Result<(libc::sockaddr_un, libc::socklen_t)> {
+    let hash = fnv_64a(format!("lxc/{}/monitor-sock", lxcpath).as_bytes());
+    let name = format!("lxc/{:016x}/{}", hash, lxcpath);
+
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    // Abstract socket: leading null byte, no trailing one
+    if name.len() + 1 >= addr.sun_path.len() {
+        return Err(Error::OperationFailed);
+    }
+
+    for (i, b) in name.bytes().enumerate() {
+        addr.sun_path[i + 1] = b as libc::c_char;
+    }
+
+    let len = mem::size_of::<libc::sa_family_t>() + name.len() + 1;
+    Ok((addr, len as libc::socklen_t))
+}
+
+/// Connect to the monitor socket of the given lxcpath.
+fn connect(lxcpath: &str) -> Result<UnixStream> {
+    let (addr, len) = socket_address(lxcpath)?;
+
+    unsafe {
+        let fd = libc::socket(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(Error::OperationFailed);
+        }
+
+        if libc::connect(fd, &addr as *const libc::sockaddr_un as *const libc::sockaddr, len) < 0 {
+            libc::close(fd);
+            return Err(Error::OperationFailed);
+        }
+
+        Ok(UnixStream::from_raw_fd(fd))
+    }
+}
+
+/// Spawn `lxc-monitord` for the given lxcpath, and wait for it
+/// to listen on the monitor socket.
+fn spawn_monitord(lxcpath: &str) -> Result<()> {
+    let path = MONITORD_PATHS.iter()
+        .find(|path| std::path::Path::new(path).exists())
+        .ok_or(Error::OperationFailed)?;
+
+    let mut fds = [0 as c_int; 2];
+    unsafe {
+        // Both ends are closed on exec, so that no other child
+        // spawned meanwhile inherits the write end
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) < 0 {
+            return Err(Error::OperationFailed);
+        }
+    }
+
+    let write_fd = fds[1];
+    let mut command = Command::new(path);
+    command.arg(lxcpath).arg(write_fd.to_string());
+
+    // Only the daemon keeps the write end across exec
+    unsafe {
+        command.pre_exec(move || {
+            if libc::fcntl(write_fd, libc::F_SETFD, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+
+    let child = command.spawn();
+
+    unsafe {
+        libc::close(write_fd);
+    }
+
+    let mut ready = unsafe { std::fs::File::from_raw_fd(fds[0]) };
+    let mut child = child.map_err(|_| Error::OperationFailed)?;
+
+    // The daemon writes a byte and closes the pipe once listening
+    let mut sync = [0u8; 1];
+    let _ = ready.read(&mut sync);
+
+    // Reap the daemon when it exits after its last client left
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+/// Open a connection to the monitor socket, spawning the monitor
+/// daemon if needed.
+fn open(lxcpath: &str) -> Result<UnixStream> {
+    connect(lxcpath).or_else(|_| {
+        spawn_monitord(lxcpath)?;
+        connect(lxcpath)
+    })
+}
+
+/// Blocking iterator over the lifecycle events of the containers
+/// of an lxcpath.
+pub struct Monitor {
+    stream: UnixStream,
+    name: Option<String>
+}
+
+impl Monitor {
+    /// Listen to the events of all the containers in the
+    /// specified lxcpath.
+    pub fn open(lxcpath: &str) -> Result<Monitor> {
+        Ok(Monitor {
+            stream: open(lxcpath)?,
+            name: None
+        })
+    }
+
+    /// Listen to the events of a single container.
+    pub fn open_container(lxcpath: &str, name: &str) -> Result<Monitor> {
+        Ok(Monitor {
+            stream: open(lxcpath)?,
+            name: Some(name.to_string())
+        })
+    }
+
+    /// Set a timeout on the wait for the next event. Once expired,
+    /// the iterator yields an error.
+    pub fn set_timeout(&self, timeout: Option<std::time::Duration>) -> Result<()> {
+        self.stream.set_read_timeout(timeout).map_err(|_| Error::OperationFailed)
+    }
+}
+
+impl Iterator for Monitor {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Result<Event>> {
+        loop {
+            let mut msg = [0u8; MSG_SIZE];
+
+            match self.stream.read_exact(&mut msg) {
+                Ok(()) => {},
+                Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return None,
+                Err(_) => return Some(Err(Error::OperationFailed))
+            }
+
+            match Event::decode(&msg) {
+                Some(ref ev) if self.name.as_ref().is_some_and(|n| n != ev.name()) => continue,
+                Some(ev) => return Some(Ok(ev)),
+                None => continue
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::nonblocking::AsyncMonitor;
+
+#[cfg(feature = "async")]
+mod nonblocking {
+    use futures_core::Stream;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, ReadBuf};
+    use tokio::net::UnixStream;
+
+    use super::{Event, MSG_SIZE};
+    use crate::{Error, Result};
+
+    /// Asynchronous stream of the lifecycle events of the containers
+    /// of an lxcpath. Must be used from within a tokio runtime.
+    pub struct AsyncMonitor {
+        stream: UnixStream,
+        name: Option<String>,
+        buf: [u8; MSG_SIZE],
+        filled: usize
+    }
+
+    impl AsyncMonitor {
+        /// Listen to the events of all the containers in the
+        /// specified lxcpath.
+        pub fn open(lxcpath: &str) -> Result<AsyncMonitor> {
+            AsyncMonitor::new(lxcpath, None)
+        }
+
+        /// Listen to the events of a single container.
+        pub fn open_container(lxcpath: &str, name: &str) -> Result<AsyncMonitor> {
+            AsyncMonitor::new(lxcpath, Some(name.to_string()))
+        }
+
+        fn new(lxcpath: &str, name: Option<String>) -> Result<AsyncMonitor> {
+            let stream = super::open(lxcpath)?;
+            stream.set_nonblocking(true).map_err(|_| Error::OperationFailed)?;
+
+            Ok(AsyncMonitor {
+                stream: UnixStream::from_std(stream).map_err(|_| Error::OperationFailed)?,
+                name,
+                buf: [0u8; MSG_SIZE],
+                filled: 0
+            })
+        }
+    }
+
+    impl Stream for AsyncMonitor {
+        type Item = Result<Event>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Event>>> {
+            let this = self.get_mut();
+
+            loop {
+                while this.filled < MSG_SIZE {
+                    let mut buf = ReadBuf::new(&mut this.buf[this.filled..]);
+
+                    match Pin::new(&mut this.stream).poll_read(cx, &mut buf) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Err(_)) => return Poll::Ready(Some(Err(Error::OperationFailed))),
+                        Poll::Ready(Ok(())) if buf.filled().is_empty() => return Poll::Ready(None),
+                        Poll::Ready(Ok(())) => this.filled += buf.filled().len()
+                    }
+                }
+
+                this.filled = 0;
+
+                match Event::decode(&this.buf) {
+                    Some(ref ev) if this.name.as_ref().is_some_and(|n| n != ev.name()) => continue,
+                    Some(ev) => return Poll::Ready(Some(Ok(ev))),
+                    None => continue
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! Supervision of the system calls that a container's seccomp policy
+//! forwards to user space, see `SeccompAction::Notify`.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+use libc::c_ulong;
+
+use super::{Error, Result};
+
+/// Configuration key of the address of the seccomp notify proxy.
+pub(crate) const SECCOMP_NOTIFY_PROXY_KEY: &str = "lxc.seccomp.notify.proxy";
+
+/// Configuration key of the cookie sent to the seccomp notify proxy.
+pub(crate) const SECCOMP_NOTIFY_COOKIE_KEY: &str = "lxc.seccomp.notify.cookie";
+
+/// Receive a notification, `_IOWR('!', 0, struct seccomp_notif)`.
+const SECCOMP_IOCTL_NOTIF_RECV: c_ulong = 0xc050_2100;
+
+/// Send a response, `_IOWR('!', 1, struct seccomp_notif_resp)`.
+const SECCOMP_IOCTL_NOTIF_SEND: c_ulong = 0xc018_2101;
+
+/// Check that a notification is still pending, `_IOW('!', 2, __u64)`,
+/// since Linux 5.7.
+const SECCOMP_IOCTL_NOTIF_ID_VALID: c_ulong = 0x4008_2102;
+
+/// Original, mistaken, value of the same request,
+/// `_IOR('!', 2, __u64)`, the only one known to Linux 5.0 to 5.6.
+const SECCOMP_IOCTL_NOTIF_ID_VALID_OLD: c_ulong = 0x8008_2102;
+
+/// Let the system call run as if it was allowed, since Linux 5.5.
+const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+
+/// Mirror of the kernel's `struct seccomp_data`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SeccompData {
+    nr: i32,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6]
+}
+
+/// Mirror of the kernel's `struct seccomp_notif`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct SeccompNotif {
+    id: u64,
+    pid: u32,
+    flags: u32,
+    data: SeccompData
+}
+
+/// Mirror of the kernel's `struct seccomp_notif_resp`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct SeccompNotifResp {
+    pub(crate) id: u64,
+    pub(crate) val: i64,
+    pub(crate) error: i32,
+    pub(crate) flags: u32
+}
+
+/// System call made by a container process and held by the kernel
+/// until the supervisor responds to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeccompNotification {
+    /// Identifier of the notification, unique for the lifetime of
+    /// the seccomp filter.
+    pub id: u64,
+
+    /// Id of the calling process, in the host's pid namespace.
+    pub pid: u32,
+
+    /// Number of the system call.
+    pub syscall: i32,
+
+    /// Architecture of the system call, as an `AUDIT_ARCH_*` value.
+    pub arch: u32,
+
+    /// Address of the instruction making the system call.
+    pub instruction_pointer: u64,
+
+    /// Arguments of the system call.
+    pub args: [u64; 6]
+}
+
+impl SeccompNotification {
+    /// Create a Rust SeccompNotification object based on
+    /// a kernel seccomp_notif struct.
+    fn from_raw(raw: &SeccompNotif) -> SeccompNotification {
+        SeccompNotification {
+            id: raw.id,
+            pid: raw.pid,
+            syscall: raw.data.nr,
+            arch: raw.data.arch,
+            instruction_pointer: raw.data.instruction_pointer,
+            args: raw.data.args
+        }
+    }
+}
+
+/// Decision of a supervisor about a notified system call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeccompResponse {
+    /// Let the system call run. This needs Linux 5.5, and must not
+    /// be used to make security decisions: the process may change
+    /// the call's arguments in memory after the check.
+    Allow,
+
+    /// Fail the system call with the given errno, which must not be
+    /// 0: the kernel would then let the call succeed with `val` 0.
+    Deny(i32),
+
+    /// Do not run the system call, and return the given value to the
+    /// process, the supervisor having done the work on its behalf.
+    Emulate(i64)
+}
+
+impl SeccompResponse {
+    /// Build the kernel seccomp_notif_resp struct answering the
+    /// notification with the given id. Fails when denying with errno 0.
+    pub(crate) fn to_raw(self, id: u64) -> Result<SeccompNotifResp> {
+        let (val, error, flags) = match self {
+            SeccompResponse::Allow => (0, 0, SECCOMP_USER_NOTIF_FLAG_CONTINUE),
+            SeccompResponse::Deny(errno) => match errno.checked_abs() {
+                Some(errno) if errno != 0 => (0, -errno, 0),
+                _ => return Err(Error::OperationFailed)
+            },
+            SeccompResponse::Emulate(val) => (val, 0, 0)
+        };
+
+        Ok(SeccompNotifResp {
+            id,
+            val,
+            error,
+            flags
+        })
+    }
+}
+
+/// Receives the system calls a container's seccomp policy forwards
+/// with the `notify` action, and answers them.
+#[derive(Debug)]
+pub struct SeccompSupervisor {
+    fd: File
+}
+
+impl SeccompSupervisor {
+    /// Create a supervisor from a seccomp notify file descriptor,
+    /// taking ownership of it.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open seccomp notify file descriptor, not used
+    /// or closed by anything else.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> SeccompSupervisor {
+        SeccompSupervisor {
+            fd: File::from_raw_fd(fd)
+        }
+    }
+
+    /// Wait for the next notification. Notifications of processes
+    /// that died before they could be received are skipped.
+    pub fn recv(&self) -> Result<SeccompNotification> {
+        loop {
+            if let Some(notif) = self.try_recv()? {
+                return Ok(notif);
+            }
+        }
+    }
+
+    /// Receive a notification, or nothing if its process died before
+    /// it could be received.
+    fn try_recv(&self) -> Result<Option<SeccompNotification>> {
+        loop {
+            unsafe {
+                // The kernel rejects a notification struct that is not
+                // zeroed
+                let mut raw: SeccompNotif = mem::zeroed();
+
+                if libc::ioctl(self.fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_RECV as _, &mut raw) == 0 {
+                    return Ok(Some(SeccompNotification::from_raw(&raw)));
+                }
+            }
+
+            match io::Error::last_os_error().raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::ENOENT) => return Ok(None),
+                _ => return Err(Error::OperationFailed)
+            }
+        }
+    }
+
+    /// Answer a notification. Answering a notification whose process
+    /// died in the meantime is not an error, denying it with errno 0 is.
+    pub fn respond(&self, notif: &SeccompNotification, response: SeccompResponse) -> Result<()> {
+        let mut raw = response.to_raw(notif.id)?;
+
+        unsafe {
+            if libc::ioctl(self.fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_SEND as _, &mut raw) == 0 {
+                return Ok(());
+            }
+        }
+
+        match io::Error::last_os_error().raw_os_error() {
+            Some(libc::ENOENT) => Ok(()),
+            _ => Err(Error::OperationFailed)
+        }
+    }
+
+    /// Check wether the notification is still pending, that is wether
+    /// its process is still blocked in the system call. This must be
+    /// checked after opening anything through the process' pid, which
+    /// may have been reused otherwise.
+    pub fn is_valid(&self, notif: &SeccompNotification) -> bool {
+        let mut id = notif.id;
+
+        unsafe {
+            if libc::ioctl(self.fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_ID_VALID as _, &mut id) == 0 {
+                return true;
+            }
+
+            io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL)
+                && libc::ioctl(self.fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_ID_VALID_OLD as _, &mut id) == 0
+        }
+    }
+
+    /// Read a NUL terminated string of at most `max_len` bytes, such as
+    /// a path argument, from the memory of the notification's process.
+    pub fn read_string(&self, notif: &SeccompNotification, addr: u64, max_len: usize) -> Result<String> {
+        let mut mem = File::open(format!("/proc/{}/mem", notif.pid)).map_err(|_| Error::OperationFailed)?;
+
+        // The pid may belong to another process if the caller died
+        // before the file was opened
+        if !self.is_valid(notif) {
+            return Err(Error::OperationFailed);
+        }
+
+        let mut buf = Vec::with_capacity(max_len);
+        mem.seek(SeekFrom::Start(addr)).map_err(|_| Error::OperationFailed)?;
+        mem.take(max_len as u64).read_to_end(&mut buf).map_err(|_| Error::OperationFailed)?;
+
+        let len = buf.iter().position(|b| *b == 0).ok_or(Error::OperationFailed)?;
+        buf.truncate(len);
+
+        String::from_utf8(buf).map_err(|_| Error::OperationFailed)
+    }
+
+    /// Answer every notification with the response chosen by the
+    /// handler, until the container stops.
+    pub fn run<F>(&self, mut handler: F) -> Result<()>
+        where F: FnMut(&SeccompSupervisor, &SeccompNotification) -> SeccompResponse {
+
+        loop {
+            let mut pfd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0
+            };
+
+            if unsafe { libc::poll(&mut pfd, 1, -1) } < 0 {
+                if io::Error::last_os_error().raw_os_error() == Some(libc::EINTR) {
+                    continue;
+                }
+
+                return Err(Error::OperationFailed);
+            }
+
+            // Pending notifications are answered before noticing that
+            // the filter has no process left
+            if pfd.revents & libc::POLLIN == 0 {
+                if pfd.revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) != 0 {
+                    return Ok(());
+                }
+
+                continue;
+            }
+
+            let notif = match self.try_recv()? {
+                Some(notif) => notif,
+                None => continue
+            };
+
+            let response = handler(self, &notif);
+            self.respond(&notif, response)?;
+        }
+    }
+}
+
+impl AsRawFd for SeccompSupervisor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
@@ -0,0 +1,156 @@
+//! Retention policies deciding which container snapshots to prune.
+
+use std::collections::HashSet;
+use std::mem;
+use std::time::UNIX_EPOCH;
+
+use super::Snapshot;
+
+/// Retention policy of a container's snapshots. A snapshot is kept
+/// if any of the rules selects it. A policy without any rule keeps
+/// every snapshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RetentionPolicy {
+    last: usize,
+    hourly: usize,
+    daily: usize,
+    weekly: usize
+}
+
+/// Outcome of a retention policy: the names of the snapshots to keep
+/// and of those to prune, newest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrunePlan {
+    /// Snapshots selected by the policy.
+    pub keep: Vec<String>,
+
+    /// Snapshots to destroy.
+    pub prune: Vec<String>
+}
+
+impl RetentionPolicy {
+    /// Create a new RetentionPolicy object, without any rule.
+    pub fn new() -> RetentionPolicy {
+        RetentionPolicy::default()
+    }
+
+    /// Keep the last `n` snapshots.
+    pub fn keep_last(mut self, n: usize) -> RetentionPolicy {
+        self.last = n;
+        self
+    }
+
+    /// Keep the newest snapshot of each of the last `n` hours
+    /// having snapshots.
+    pub fn keep_hourly(mut self, n: usize) -> RetentionPolicy {
+        self.hourly = n;
+        self
+    }
+
+    /// Keep the newest snapshot of each of the last `n` days
+    /// having snapshots.
+    pub fn keep_daily(mut self, n: usize) -> RetentionPolicy {
+        self.daily = n;
+        self
+    }
+
+    /// Keep the newest snapshot of each of the last `n` weeks
+    /// having snapshots. Weeks start on monday.
+    pub fn keep_weekly(mut self, n: usize) -> RetentionPolicy {
+        self.weekly = n;
+        self
+    }
+
+    /// Compute which of the given snapshots to keep and which to
+    /// prune. Snapshots whose creation time cannot be determined
+    /// are always kept.
+    pub fn plan(&self, snapshots: &[Snapshot]) -> PrunePlan {
+        let mut plan = PrunePlan::default();
+
+        let mut dated = Vec::new();
+        for snap in snapshots {
+            match local_seconds(snap) {
+                Some(secs) => dated.push((secs, snap.name.clone())),
+                None => plan.keep.push(snap.name.clone())
+            }
+        }
+
+        // Newest first
+        dated.sort_by(|a, b| b.cmp(a));
+
+        if *self == RetentionPolicy::default() {
+            plan.keep.extend(dated.into_iter().map(|(_, name)| name));
+            return plan;
+        }
+
+        let mut keep = HashSet::new();
+        keep.extend(dated.iter().take(self.last).map(|(_, name)| name.clone()));
+
+        let buckets: [(usize, Bucket); 3] = [
+            (self.hourly, hour),
+            (self.daily, day),
+            (self.weekly, week)
+        ];
+
+        for (count, bucket) in buckets.iter() {
+            let mut seen = HashSet::new();
+
+            for (secs, name) in dated.iter() {
+                if seen.len() >= *count {
+                    break;
+                }
+
+                // The newest snapshot of each bucket is met first
+                if seen.insert(bucket(*secs)) {
+                    keep.insert(name.clone());
+                }
+            }
+        }
+
+        for (_, name) in dated {
+            if keep.contains(&name) {
+                plan.keep.push(name);
+            }
+            else {
+                plan.prune.push(name);
+            }
+        }
+
+        plan
+    }
+}
+
+/// Function mapping a time, in seconds, to the period it is in.
+type Bucket = fn(i64) -> i64;
+
+/// Get the hour of a time, in seconds.
+fn hour(secs: i64) -> i64 {
+    secs.div_euclid(3600)
+}
+
+/// Get the day of a time, in seconds.
+fn day(secs: i64) -> i64 {
+    secs.div_euclid(86400)
+}
+
+/// Get the week of a time, in seconds. 1970-01-01 was a thursday,
+/// weeks start on monday.
+fn week(secs: i64) -> i64 {
+    (day(secs) + 3).div_euclid(7)
+}
+
+/// Get the creation time of a snapshot, in seconds since the epoch
+/// shifted to the local timezone, so that hours and days are
+/// bucketed as the user sees them.
+fn local_seconds(snap: &Snapshot) -> Option<i64> {
+    let secs = snap.created_at()?.duration_since(UNIX_EPOCH).ok()?.as_secs() as libc::time_t;
+
+    unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        if libc::localtime_r(&secs, &mut tm).is_null() {
+            return None;
+        }
+
+        Some(secs as i64 + tm.tm_gmtoff as i64)
+    }
+}
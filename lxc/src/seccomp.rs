@@ -0,0 +1,589 @@
+//! Generation and validation of LXC seccomp policy files, in the
+//! version 2 format.
+//!
+//! The action on the header line applies to the system calls the
+//! policy does not list, and rules without an action kill the
+//! process in a denylist, or allow the call in an allowlist.
+//!
+//! ```text
+//! 2
+//! blacklist
+//! [all]
+//! init_module errno 1
+//! keyctl errno 38
+//! [x86_64]
+//! personality kill [0,8,SCMP_CMP_EQ]
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{Error, Result};
+
+/// Configuration key of the seccomp policy file. liblxc 2.1 already
+/// understands it, and 3.0 removed the legacy `lxc.seccomp`.
+pub(crate) const SECCOMP_PROFILE_KEY: &str = "lxc.seccomp.profile";
+
+/// Largest errno value a seccomp filter can return.
+const MAX_ERRNO: u32 = 4095;
+
+/// Number of arguments of a system call.
+const MAX_ARGS: u32 = 6;
+
+/// Action taken when a system call matches a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeccompAction {
+    /// Kill the process.
+    Kill,
+
+    /// Allow the system call.
+    Allow,
+
+    /// Send `SIGSYS` to the process.
+    Trap,
+
+    /// Fail the system call with the given errno.
+    Errno(u32),
+
+    /// Forward the system call to a supervisor, see `lxc.seccomp.notify.proxy`.
+    /// Requires liblxc 4.0.
+    Notify
+}
+
+impl fmt::Display for SeccompAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SeccompAction::Kill => write!(f, "kill"),
+            SeccompAction::Allow => write!(f, "allow"),
+            SeccompAction::Trap => write!(f, "trap"),
+            SeccompAction::Errno(errno) => write!(f, "errno {}", errno),
+            SeccompAction::Notify => write!(f, "notify")
+        }
+    }
+}
+
+/// Comparison applied to a system call argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeccompCompare {
+    /// The argument is not equal to the value.
+    Ne,
+
+    /// The argument is lower than the value.
+    Lt,
+
+    /// The argument is lower than or equal to the value.
+    Le,
+
+    /// The argument is equal to the value.
+    Eq,
+
+    /// The argument is greater than or equal to the value.
+    Ge,
+
+    /// The argument is greater than the value.
+    Gt,
+
+    /// The argument, masked with the given mask, is equal
+    /// to the value.
+    MaskedEq(u64)
+}
+
+impl SeccompCompare {
+    /// Name of the comparison operator, as used by libseccomp.
+    fn as_str(&self) -> &'static str {
+        match *self {
+            SeccompCompare::Ne => "SCMP_CMP_NE",
+            SeccompCompare::Lt => "SCMP_CMP_LT",
+            SeccompCompare::Le => "SCMP_CMP_LE",
+            SeccompCompare::Eq => "SCMP_CMP_EQ",
+            SeccompCompare::Ge => "SCMP_CMP_GE",
+            SeccompCompare::Gt => "SCMP_CMP_GT",
+            SeccompCompare::MaskedEq(_) => "SCMP_CMP_MASKED_EQ"
+        }
+    }
+}
+
+/// Filter on an argument of a system call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SeccompArg {
+    /// Index of the argument, from 0 to 5.
+    pub index: u32,
+
+    /// Value the argument is compared to.
+    pub value: u64,
+
+    /// Comparison applied.
+    pub op: SeccompCompare
+}
+
+impl SeccompArg {
+    /// Create a new SeccompArg object.
+    pub fn new(index: u32, value: u64, op: SeccompCompare) -> SeccompArg {
+        SeccompArg {
+            index,
+            value,
+            op
+        }
+    }
+}
+
+impl fmt::Display for SeccompArg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.op {
+            SeccompCompare::MaskedEq(mask) => {
+                write!(f, "[{},{},{},{}]", self.index, self.value, self.op.as_str(), mask)
+            },
+            _ => write!(f, "[{},{},{}]", self.index, self.value, self.op.as_str())
+        }
+    }
+}
+
+impl FromStr for SeccompArg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SeccompArg> {
+        let inner = s.strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or(Error::OperationFailed)?;
+
+        let fields = inner.split(',').map(|f| f.trim()).collect::<Vec<&str>>();
+        if fields.len() < 3 || fields.len() > 4 {
+            return Err(Error::OperationFailed);
+        }
+
+        let index = fields[0].parse().map_err(|_| Error::OperationFailed)?;
+        let value = parse_u64(fields[1])?;
+
+        let op = match (fields[2], fields.get(3)) {
+            ("SCMP_CMP_NE", None) => SeccompCompare::Ne,
+            ("SCMP_CMP_LT", None) => SeccompCompare::Lt,
+            ("SCMP_CMP_LE", None) => SeccompCompare::Le,
+            ("SCMP_CMP_EQ", None) => SeccompCompare::Eq,
+            ("SCMP_CMP_GE", None) => SeccompCompare::Ge,
+            ("SCMP_CMP_GT", None) => SeccompCompare::Gt,
+            ("SCMP_CMP_MASKED_EQ", Some(mask)) => SeccompCompare::MaskedEq(parse_u64(mask)?),
+            _ => return Err(Error::OperationFailed)
+        };
+
+        Ok(SeccompArg::new(index, value, op))
+    }
+}
+
+/// Parse a decimal or hexadecimal (`0x` prefixed) value.
+fn parse_u64(s: &str) -> Result<u64> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse()
+    };
+
+    parsed.map_err(|_| Error::OperationFailed)
+}
+
+/// Rule matching a system call.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SeccompRule {
+    /// Name of the system call.
+    pub syscall: String,
+
+    /// Action taken, if `None` the process is killed for a denylist
+    /// and the call allowed for an allowlist.
+    pub action: Option<SeccompAction>,
+
+    /// Filters on the arguments, all of them must match.
+    pub args: Vec<SeccompArg>
+}
+
+impl SeccompRule {
+    /// Create a new SeccompRule object for the given system call.
+    pub fn new<S: Into<String>>(syscall: S) -> SeccompRule {
+        SeccompRule {
+            syscall: syscall.into(),
+            action: None,
+            args: Vec::new()
+        }
+    }
+
+    /// Set the action taken when the rule matches.
+    pub fn action(mut self, action: SeccompAction) -> SeccompRule {
+        self.action = Some(action);
+        self
+    }
+
+    /// Only match the system call when its argument matches.
+    pub fn arg(mut self, arg: SeccompArg) -> SeccompRule {
+        self.args.push(arg);
+        self
+    }
+}
+
+impl fmt::Display for SeccompRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.syscall)?;
+
+        if let Some(action) = self.action {
+            write!(f, " {}", action)?;
+        }
+
+        for arg in self.args.iter() {
+            write!(f, " {}", arg)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SeccompRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SeccompRule> {
+        let mut tokens = s.split_whitespace().peekable();
+        let mut rule = SeccompRule::new(tokens.next().ok_or(Error::OperationFailed)?);
+
+        rule.action = match tokens.peek() {
+            Some(token) if !token.starts_with('[') => {
+                let token = tokens.next().unwrap_or_default();
+                Some(parse_action(token, || tokens.next())?)
+            },
+            _ => None
+        };
+
+        for token in tokens {
+            rule.args.push(token.parse()?);
+        }
+
+        Ok(rule)
+    }
+}
+
+/// Parse an action, reading the errno value if needed.
+fn parse_action<'a, F>(token: &str, mut next: F) -> Result<SeccompAction>
+    where F: FnMut() -> Option<&'a str>
+{
+    match token {
+        "kill" => Ok(SeccompAction::Kill),
+        "allow" => Ok(SeccompAction::Allow),
+        "trap" => Ok(SeccompAction::Trap),
+        "notify" => Ok(SeccompAction::Notify),
+        "errno" => {
+            let errno = next().ok_or(Error::OperationFailed)?;
+            errno.parse().map(SeccompAction::Errno).map_err(|_| Error::OperationFailed)
+        },
+        _ => Err(Error::OperationFailed)
+    }
+}
+
+/// Architecture a section of rules applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeccompArch {
+    /// Every architecture supported by the host.
+    All,
+    X86_64,
+    I386,
+    X32,
+    Arm,
+    Arm64,
+    Ppc,
+    Ppc64,
+    Ppc64le,
+    Mips,
+    Mipsel,
+    Mips64,
+    Mips64n32,
+    Mipsel64,
+    Mipsel64n32,
+    S390,
+    S390x,
+    Riscv64
+}
+
+impl SeccompArch {
+    /// Name of the architecture, as used in section headers.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            SeccompArch::All => "all",
+            SeccompArch::X86_64 => "x86_64",
+            SeccompArch::I386 => "x86",
+            SeccompArch::X32 => "x32",
+            SeccompArch::Arm => "arm",
+            SeccompArch::Arm64 => "arm64",
+            SeccompArch::Ppc => "ppc",
+            SeccompArch::Ppc64 => "ppc64",
+            SeccompArch::Ppc64le => "ppc64le",
+            SeccompArch::Mips => "mips",
+            SeccompArch::Mipsel => "mipsel",
+            SeccompArch::Mips64 => "mips64",
+            SeccompArch::Mips64n32 => "mips64n32",
+            SeccompArch::Mipsel64 => "mipsel64",
+            SeccompArch::Mipsel64n32 => "mipsel64n32",
+            SeccompArch::S390 => "s390",
+            SeccompArch::S390x => "s390x",
+            SeccompArch::Riscv64 => "riscv64"
+        }
+    }
+
+    /// Get all the architectures.
+    pub fn all() -> &'static [SeccompArch] {
+        &[
+            SeccompArch::All, SeccompArch::X86_64, SeccompArch::I386, SeccompArch::X32,
+            SeccompArch::Arm, SeccompArch::Arm64, SeccompArch::Ppc, SeccompArch::Ppc64,
+            SeccompArch::Ppc64le, SeccompArch::Mips, SeccompArch::Mipsel, SeccompArch::Mips64,
+            SeccompArch::Mips64n32, SeccompArch::Mipsel64, SeccompArch::Mipsel64n32,
+            SeccompArch::S390, SeccompArch::S390x, SeccompArch::Riscv64
+        ]
+    }
+}
+
+impl FromStr for SeccompArch {
+    type Err = Error;
+
+    /// Parse a section header name, in any case. `i386` is accepted
+    /// as an alias of liblxc's `x86`.
+    fn from_str(s: &str) -> Result<SeccompArch> {
+        let name = s.to_ascii_lowercase();
+        if name == "i386" {
+            return Ok(SeccompArch::I386);
+        }
+
+        SeccompArch::all()
+            .iter()
+            .find(|arch| arch.as_str() == name)
+            .cloned()
+            .ok_or(Error::OperationFailed)
+    }
+}
+
+/// Wether a policy lists the denied or the allowed system calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeccompMode {
+    /// Listed system calls are denied, the others allowed.
+    Denylist,
+
+    /// Listed system calls are allowed, the others denied.
+    Allowlist
+}
+
+/// LXC seccomp policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeccompPolicy {
+    mode: SeccompMode,
+    default_action: Option<SeccompAction>,
+    sections: Vec<(SeccompArch, Vec<SeccompRule>)>
+}
+
+impl SeccompPolicy {
+    /// Create a new policy denying the listed system calls.
+    pub fn denylist() -> SeccompPolicy {
+        SeccompPolicy::new(SeccompMode::Denylist)
+    }
+
+    /// Create a new policy only allowing the listed system calls.
+    pub fn allowlist() -> SeccompPolicy {
+        SeccompPolicy::new(SeccompMode::Allowlist)
+    }
+
+    /// Create a new empty policy.
+    pub fn new(mode: SeccompMode) -> SeccompPolicy {
+        SeccompPolicy {
+            mode,
+            default_action: None,
+            sections: Vec::new()
+        }
+    }
+
+    /// Policy equivalent to LXC's own `common.seccomp`: denies kernel
+    /// module loading, kexec, open_by_handle_at and forced umounts
+    /// with `EPERM`, and allows everything else.
+    pub fn lxc_default() -> SeccompPolicy {
+        SeccompPolicy::denylist()
+            .rule(SeccompArch::All, SeccompRule::new("reject_force_umount"))
+            .deny_module_loading()
+            .deny_kexec()
+            .deny_open_by_handle()
+    }
+
+    /// Mode of the policy.
+    pub fn mode(&self) -> SeccompMode {
+        self.mode
+    }
+
+    /// Get the rules of the given architecture section.
+    pub fn rules(&self, arch: SeccompArch) -> &[SeccompRule] {
+        self.sections.iter()
+            .find(|(a, _)| *a == arch)
+            .map_or(&[], |(_, rules)| rules.as_slice())
+    }
+
+    /// Set the action taken on the system calls the policy does not
+    /// list, written on the header line. Without it, they are allowed
+    /// by a denylist and kill the process with an allowlist. This
+    /// does not apply to the rules without an action.
+    pub fn default_action(mut self, action: SeccompAction) -> SeccompPolicy {
+        self.default_action = Some(action);
+        self
+    }
+
+    /// Add a rule to the given architecture section.
+    pub fn rule(mut self, arch: SeccompArch, rule: SeccompRule) -> SeccompPolicy {
+        match self.sections.iter_mut().find(|(a, _)| *a == arch) {
+            Some((_, rules)) => rules.push(rule),
+            None => self.sections.push((arch, vec![rule]))
+        }
+
+        self
+    }
+
+    /// Make the given system call fail with the given errno, on
+    /// every architecture.
+    pub fn deny(self, syscall: &str, errno: u32) -> SeccompPolicy {
+        self.rule(SeccompArch::All, SeccompRule::new(syscall).action(SeccompAction::Errno(errno)))
+    }
+
+    /// Allow the given system call on every architecture.
+    pub fn allow(self, syscall: &str) -> SeccompPolicy {
+        self.rule(SeccompArch::All, SeccompRule::new(syscall).action(SeccompAction::Allow))
+    }
+
+    /// Forward the given system call to the seccomp notify
+    /// supervisor, on every architecture.
+    pub fn notify(self, syscall: &str) -> SeccompPolicy {
+        self.rule(SeccompArch::All, SeccompRule::new(syscall).action(SeccompAction::Notify))
+    }
+
+    /// Deny loading and unloading kernel modules.
+    pub fn deny_module_loading(self) -> SeccompPolicy {
+        self.deny("init_module", libc::EPERM as u32)
+            .deny("finit_module", libc::EPERM as u32)
+            .deny("delete_module", libc::EPERM as u32)
+    }
+
+    /// Deny the kernel keyring, which is not namespaced.
+    pub fn deny_keyctl(self) -> SeccompPolicy {
+        self.deny("keyctl", libc::ENOSYS as u32)
+            .deny("add_key", libc::ENOSYS as u32)
+            .deny("request_key", libc::ENOSYS as u32)
+    }
+
+    /// Deny loading a new kernel.
+    pub fn deny_kexec(self) -> SeccompPolicy {
+        self.deny("kexec_load", libc::EPERM as u32)
+            .deny("kexec_file_load", libc::EPERM as u32)
+    }
+
+    /// Deny opening files by handle, which allows escaping
+    /// bind mounts.
+    pub fn deny_open_by_handle(self) -> SeccompPolicy {
+        self.deny("open_by_handle_at", libc::EPERM as u32)
+    }
+
+    /// Check that the policy can be loaded by liblxc: syscall names
+    /// are set, errno values and argument indexes are in range.
+    pub fn validate(&self) -> Result<()> {
+        let valid_action = |action: &SeccompAction| match *action {
+            SeccompAction::Errno(errno) => errno <= MAX_ERRNO,
+            _ => true
+        };
+
+        if !self.default_action.iter().all(valid_action) {
+            return Err(Error::OperationFailed);
+        }
+
+        for (_, rules) in self.sections.iter() {
+            for rule in rules {
+                let valid = !rule.syscall.is_empty()
+                    && rule.action.iter().all(valid_action)
+                    && rule.args.iter().all(|arg| arg.index < MAX_ARGS);
+
+                if !valid {
+                    return Err(Error::OperationFailed);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse and validate a policy file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SeccompPolicy> {
+        fs::read_to_string(path).map_err(|_| Error::OperationFailed)?.parse()
+    }
+
+    /// Validate the policy and write it to the given file.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.validate()?;
+        fs::write(path, self.to_string()).map_err(|_| Error::OperationFailed)
+    }
+}
+
+impl fmt::Display for SeccompPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "2")?;
+
+        match self.mode {
+            SeccompMode::Denylist => write!(f, "blacklist")?,
+            SeccompMode::Allowlist => write!(f, "whitelist")?
+        }
+
+        // The header action applies to the unlisted system calls
+        match self.default_action {
+            Some(action) => writeln!(f, " {}", action)?,
+            None => writeln!(f)?
+        }
+
+        for (arch, rules) in self.sections.iter() {
+            writeln!(f, "[{}]", arch.as_str())?;
+
+            for rule in rules {
+                writeln!(f, "{}", rule)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SeccompPolicy {
+    type Err = Error;
+
+    /// Parse a version 2 policy, and validate it.
+    fn from_str(s: &str) -> Result<SeccompPolicy> {
+        let mut lines = s.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        if lines.next() != Some("2") {
+            return Err(Error::OperationFailed);
+        }
+
+        let header = lines.next().ok_or(Error::OperationFailed)?;
+        let mut tokens = header.split_whitespace();
+
+        let mut policy = match tokens.next() {
+            Some("blacklist") | Some("denylist") => SeccompPolicy::denylist(),
+            Some("whitelist") | Some("allowlist") => SeccompPolicy::allowlist(),
+            _ => return Err(Error::OperationFailed)
+        };
+
+        if let Some(token) = tokens.next() {
+            policy.default_action = Some(parse_action(token, || tokens.next())?);
+        }
+
+        // Rules before any section apply to all architectures
+        let mut arch = SeccompArch::All;
+
+        for line in lines {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                arch = name.parse()?;
+                continue;
+            }
+
+            // Strip trailing comments
+            let line = line.split('#').next().unwrap_or_default();
+            policy = policy.rule(arch, line.parse()?);
+        }
+
+        policy.validate()?;
+        Ok(policy)
+    }
+}
@@ -0,0 +1,188 @@
+//! Typed access to the security related configuration items of a
+//! container: LSM profiles, capabilities and no_new_privs.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Error, Result};
+
+/// Configuration key of the AppArmor profile. liblxc 2.1 already
+/// understands it, and 3.0 removed the legacy `lxc.aa_profile`.
+pub(crate) const APPARMOR_PROFILE_KEY: &str = "lxc.apparmor.profile";
+
+/// Configuration key allowing nesting under AppArmor, introduced
+/// in liblxc 3.0.
+#[cfg(feature = "v3_0")]
+pub(crate) const APPARMOR_ALLOW_NESTING_KEY: &str = "lxc.apparmor.allow_nesting";
+
+/// Configuration key of the SELinux context. liblxc 2.1 already
+/// understands it, and 3.0 removed the legacy `lxc.se_context`.
+pub(crate) const SELINUX_CONTEXT_KEY: &str = "lxc.selinux.context";
+
+/// Configuration key of the capabilities to drop.
+pub(crate) const CAP_DROP_KEY: &str = "lxc.cap.drop";
+
+/// Configuration key of the capabilities to keep.
+pub(crate) const CAP_KEEP_KEY: &str = "lxc.cap.keep";
+
+/// Configuration key of the no_new_privs flag.
+pub(crate) const NO_NEW_PRIVS_KEY: &str = "lxc.no_new_privs";
+
+macro_rules! capabilities {
+    ($($variant:ident => $name:expr),*) => {
+        /// Linux capability, as named in `lxc.cap.drop` and
+        /// `lxc.cap.keep`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Capability {
+            $($variant),*
+        }
+
+        impl Capability {
+            /// Name of the capability, without the `CAP_` prefix
+            /// and in lower case, as used by liblxc.
+            pub fn as_str(&self) -> &'static str {
+                match *self {
+                    $(Capability::$variant => $name),*
+                }
+            }
+
+            /// Get all the capabilities, in the order of their numbers.
+            pub fn all() -> &'static [Capability] {
+                &[$(Capability::$variant),*]
+            }
+        }
+    };
+}
+
+capabilities! {
+    Chown => "chown",
+    DacOverride => "dac_override",
+    DacReadSearch => "dac_read_search",
+    Fowner => "fowner",
+    Fsetid => "fsetid",
+    Kill => "kill",
+    Setgid => "setgid",
+    Setuid => "setuid",
+    Setpcap => "setpcap",
+    LinuxImmutable => "linux_immutable",
+    NetBindService => "net_bind_service",
+    NetBroadcast => "net_broadcast",
+    NetAdmin => "net_admin",
+    NetRaw => "net_raw",
+    IpcLock => "ipc_lock",
+    IpcOwner => "ipc_owner",
+    SysModule => "sys_module",
+    SysRawio => "sys_rawio",
+    SysChroot => "sys_chroot",
+    SysPtrace => "sys_ptrace",
+    SysPacct => "sys_pacct",
+    SysAdmin => "sys_admin",
+    SysBoot => "sys_boot",
+    SysNice => "sys_nice",
+    SysResource => "sys_resource",
+    SysTime => "sys_time",
+    SysTtyConfig => "sys_tty_config",
+    Mknod => "mknod",
+    Lease => "lease",
+    AuditWrite => "audit_write",
+    AuditControl => "audit_control",
+    Setfcap => "setfcap",
+    MacOverride => "mac_override",
+    MacAdmin => "mac_admin",
+    Syslog => "syslog",
+    WakeAlarm => "wake_alarm",
+    BlockSuspend => "block_suspend",
+    AuditRead => "audit_read",
+    Perfmon => "perfmon",
+    Bpf => "bpf",
+    CheckpointRestore => "checkpoint_restore"
+}
+
+impl Capability {
+    /// Number of the capabilities added in Linux 5.8 and 5.9. liblxc
+    /// releases predating them only accept them by number.
+    fn recent_number(&self) -> Option<u32> {
+        match *self {
+            Capability::Perfmon => Some(38),
+            Capability::Bpf => Some(39),
+            Capability::CheckpointRestore => Some(40),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = Error;
+
+    /// Parse a capability name, with or without the `CAP_` prefix,
+    /// in any case.
+    fn from_str(s: &str) -> Result<Capability> {
+        let name = s.to_ascii_lowercase();
+        let name = name.strip_prefix("cap_").unwrap_or(&name);
+
+        Capability::all()
+            .iter()
+            .find(|cap| cap.as_str() == name)
+            .cloned()
+            .ok_or(Error::OperationFailed)
+    }
+}
+
+/// Format a list of capabilities as a configuration value. Recent
+/// capabilities are written by number, for older liblxc releases.
+pub(crate) fn format_caps(caps: &[Capability]) -> String {
+    caps.iter()
+        .map(|cap| match cap.recent_number() {
+            Some(number) => number.to_string(),
+            None => cap.as_str().to_string()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Parse a list of capabilities, given by name or number, from a
+/// configuration value. The special `none` keep value yields an
+/// empty list.
+pub(crate) fn parse_caps(value: &str) -> Result<Vec<Capability>> {
+    value.split_whitespace()
+        .filter(|name| *name != "none")
+        .map(|name| match name.parse::<u32>() {
+            Ok(number) => Capability::all()
+                .get(number as usize)
+                .cloned()
+                .ok_or(Error::OperationFailed),
+            Err(_) => name.parse()
+        })
+        .collect()
+}
+
+/// Check that the running kernel knows every capability of the list.
+/// Returns `Error::Unsupported` for the recent capabilities it does
+/// not know, as the container would then fail to start.
+pub(crate) fn check_caps(caps: &[Capability]) -> Result<()> {
+    let last = match std::fs::read_to_string("/proc/sys/kernel/cap_last_cap") {
+        Ok(last) => last.trim().parse::<u32>().map_err(|_| Error::OperationFailed)?,
+        Err(_) => return Ok(())
+    };
+
+    if caps.iter().filter_map(|cap| cap.recent_number()).any(|number| number > last) {
+        return Err(Error::Unsupported);
+    }
+
+    Ok(())
+}
+
+/// Parse a boolean configuration value.
+pub(crate) fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim() {
+        "" | "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(Error::OperationFailed)
+    }
+}
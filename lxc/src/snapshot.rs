@@ -0,0 +1,232 @@
+//! Container snapshots and their metadata.
+
+use crate::lib;
+use std::ffi::{CStr, CString, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::mem;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use libc::c_char;
+
+use super::{create, BackingStore, Container, Error, Result};
+
+/// Name of the file holding a snapshot's tags, one per line, in
+/// the snapshot's directory.
+pub(crate) const TAGS_FILE: &str = "tags";
+
+/// Represents an LXC container snapshot.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Name of the snapshot.
+    pub name: String,
+
+    /// Time the snapshot was created at.
+    pub created: String,
+
+    /// Comment attached to the snapshot, if any.
+    pub comment: Option<String>,
+
+    /// Tags attached to the snapshot.
+    pub tags: Vec<String>,
+
+    /// Path of the directory holding the snapshot, which acts as
+    /// the lxcpath of the snapshot's container.
+    pub lxcpath: String
+}
+
+impl Snapshot {
+    /// Create a Rust Snapshot object based on a
+    /// liblxc lxc_snapshot struct, copying its fields.
+    fn from_raw(raw: &lib::lxc_snapshot) -> Snapshot {
+        unsafe {
+            let string = |ptr: *mut c_char| {
+                if ptr.is_null() {
+                    return String::new();
+                }
+
+                CStr::from_ptr(ptr as *const c_char).to_string_lossy().into_owned()
+            };
+
+            // The comment file only exists if a comment was given
+            let comment = if raw.comment_pathname.is_null() {
+                None
+            }
+            else {
+                std::fs::read_to_string(string(raw.comment_pathname)).ok()
+            };
+
+            let name = string(raw.name);
+            let lxcpath = string(raw.lxcpath);
+
+            let tags = std::fs::read_to_string(format!("{}/{}/{}", lxcpath, name, TAGS_FILE))
+                .map(|tags| tags.lines().map(|t| t.to_string()).collect())
+                .unwrap_or_default();
+
+            Snapshot {
+                name,
+                created: string(raw.timestamp),
+                comment,
+                tags,
+                lxcpath
+            }
+        }
+    }
+
+    /// Parse the time the snapshot was created at. liblxc records
+    /// it in local time, as `YYYY:MM:DD HH:MM:SS`.
+    pub fn created_at(&self) -> Option<SystemTime> {
+        let fields = self.created
+            .split([':', ' '])
+            .map(|f| f.parse::<i32>().ok())
+            .collect::<Option<Vec<i32>>>()?;
+
+        if fields.len() != 6 {
+            return None;
+        }
+
+        let secs = unsafe {
+            let mut tm: libc::tm = mem::zeroed();
+            tm.tm_year = fields[0] - 1900;
+            tm.tm_mon = fields[1] - 1;
+            tm.tm_mday = fields[2];
+            tm.tm_hour = fields[3];
+            tm.tm_min = fields[4];
+            tm.tm_sec = fields[5];
+            tm.tm_isdst = -1;
+
+            libc::mktime(&mut tm)
+        };
+
+        if secs < 0 {
+            return None;
+        }
+
+        Some(UNIX_EPOCH + Duration::from_secs(secs as u64))
+    }
+
+    /// Create a new container with the given name, in the specified
+    /// lxcpath, from the snapshot. The clone is made on the given
+    /// backing store, the snapshot's own by default. With
+    /// `copy_on_write`, the clone shares the snapshot's data, which
+    /// requires an overlay, btrfs, zfs or lvm backing store.
+    pub fn clone_to(&self, name: &str, lxcpath: &str, bdev: Option<BackingStore>, copy_on_write: bool) -> Result<Container> {
+        let flags = if copy_on_write { lib::LXC_CLONE_SNAPSHOT } else { 0 };
+
+        Container::get(&self.lxcpath, &self.name)?
+            .clone_with(name, lxcpath, bdev, flags)
+    }
+
+    /// Open the snapshot as a container, to inspect its
+    /// configuration and rootfs.
+    pub fn open(&self) -> Result<SnapshotContainer> {
+        Ok(SnapshotContainer {
+            inner: Container::get(&self.lxcpath, &self.name)?
+        })
+    }
+}
+
+/// Private temporary directory holding the comment file of a new
+/// snapshot, removed when dropped.
+pub(crate) struct CommentDir {
+    path: PathBuf
+}
+
+impl CommentDir {
+    /// Create a directory with a random name, only accessible to
+    /// the current user.
+    pub(crate) fn new() -> Result<CommentDir> {
+        let template = std::env::temp_dir().join("lxc-rs-comment-XXXXXX");
+        let template = CString::new(template.as_os_str().as_bytes()).map_err(|_| Error::OperationFailed)?;
+        let mut template = template.into_bytes_with_nul();
+
+        unsafe {
+            if libc::mkdtemp(template.as_mut_ptr() as *mut c_char).is_null() {
+                return Err(Error::OperationFailed);
+            }
+        }
+
+        template.pop();
+
+        Ok(CommentDir {
+            path: PathBuf::from(OsString::from_vec(template))
+        })
+    }
+
+    /// Write the comment to a new file of the directory, and return
+    /// the file's path.
+    pub(crate) fn write(&self, comment: &str) -> Result<String> {
+        let path = self.path.join("comment");
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .map_err(|_| Error::OperationFailed)?;
+
+        file.write_all(comment.as_bytes()).map_err(|_| Error::OperationFailed)?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+}
+
+impl Drop for CommentDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Build the Snapshot objects from the array returned by liblxc's
+/// `snapshot_list`, and release the array. The strings of each entry
+/// are freed by the entry's own `free` function, the array itself
+/// was allocated with `malloc`.
+pub(crate) unsafe fn snapshots_from_raw(snaps: *mut lib::lxc_snapshot, count: usize) -> Vec<Snapshot> {
+    let mut vec = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let entry = snaps.add(i);
+        vec.push(Snapshot::from_raw(&*entry));
+
+        if let Some(free) = (*entry).free {
+            free(entry);
+        }
+    }
+
+    libc::free(snaps as *mut libc::c_void);
+    vec
+}
+
+/// Read-only view of the container stored in a snapshot.
+#[derive(Debug)]
+pub struct SnapshotContainer {
+    inner: Container
+}
+
+impl SnapshotContainer {
+    /// Name of the snapshot's container.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Get the configuration file name of the snapshot's
+    /// container.
+    pub fn get_config_file_name(&self) -> Result<String> {
+        self.inner.get_config_file_name()
+    }
+
+    /// Retreive the value of a configuration item of the
+    /// snapshot's container.
+    pub fn get_config_item(&self, key: &str) -> Result<String> {
+        self.inner.get_config_item(key)
+    }
+
+    /// Get the path of the directory holding the snapshot's
+    /// rootfs.
+    pub fn rootfs(&self) -> Result<String> {
+        let path = self.inner.get_config_item(create::ROOTFS_KEY)?;
+        Ok(create::rootfs_dir(&path).to_string())
+    }
+}
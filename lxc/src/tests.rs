@@ -1,12 +1,23 @@
-/// Tests module.
+//! Tests module.
 
-use super::{Container, Template};
+use libc::c_void;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-const LXC_PATH: &'static str = "/var/lib/lxc";
+use super::{create, lib, security, snapshot};
+use super::{
+    BackingStore, Capability, Container, CreateOptions, CriuFeatures, Event, GlobalConfig, Hook, HookVersion,
+    IdKind, IdMap, MigrateCommand, MigrateOptions, Monitor, RetentionPolicy, Rootfs, SeccompAction,
+    SeccompArch, SeccompArg, SeccompCompare, SeccompMode, SeccompPolicy, SeccompRule, Snapshot, State,
+    Template, Version
+};
+
+const LXC_PATH: &str = "/var/lib/lxc";
 
 #[test]
 fn version() {
-    assert!(super::version().len() > 0);
+    assert!(!super::version().is_empty());
 }
 
 #[test]
@@ -25,12 +36,12 @@ fn create_get_start_freeze_unfreeze_stop_destroy_container() {
     ct.start().unwrap();
 
     // Verify its state
-    assert!(ct.is_running());
-    assert_eq!(ct.state(), "RUNNING");
+    assert!(ct.is_running().unwrap());
+    assert_eq!(ct.state().unwrap(), "RUNNING");
 
     // Verify that it can be frozen
     ct.freeze().unwrap();
-    assert_eq!(ct.state(), "FROZEN");
+    assert_eq!(ct.state().unwrap(), "FROZEN");
 
     // Verify that it can be unfrozen
     ct.unfreeze().unwrap();
@@ -93,12 +104,12 @@ fn create_snapshot_restore_container() {
 
     // Verify that the listing contains the snapshot
     let snaps = ct.snapshot_list().unwrap();
-    assert!(snaps.len() > 0);
+    assert!(!snaps.is_empty());
 
     // Verify snapshot's information
     let snap = &snaps[0];
     assert_eq!(snap.name.as_str(), "snap0");
-    assert!(snap.created.len() > 0);
+    assert!(!snap.created.is_empty());
 
     // Restore the created snapshot
     ct.snapshot_restore(snap.name.as_str(), ct.name.as_str()).unwrap();
@@ -109,3 +120,805 @@ fn create_snapshot_restore_container() {
     // Destroy the container
     ct.destroy().unwrap();
 }
+
+#[test]
+fn create_start_run_in_container() {
+    // Create and start a container
+    let ct = Container::create(LXC_PATH, "tomme", Template::new("debian")).unwrap();
+    ct.start().unwrap();
+
+    // Verify that a closure runs inside the container's namespaces
+    let hostname = ct.run_in(|| {
+        std::fs::read_to_string("/etc/hostname").unwrap().trim().to_string()
+    }).unwrap();
+
+    assert_eq!(hostname.as_str(), "tomme");
+
+    // Stop and destroy the container
+    ct.stop().unwrap();
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn create_list_container_names() {
+    // Create a container
+    let ct = Container::create(LXC_PATH, "morbier", Template::new("debian")).unwrap();
+
+    // Verify that it is listed as defined but not as active
+    let names = Container::list_names(LXC_PATH).unwrap();
+    assert!(names.iter().any(|n| n == "morbier"));
+
+    let names = Container::list_all_names(LXC_PATH).unwrap();
+    assert!(names.iter().any(|n| n == "morbier"));
+
+    let names = Container::list_active_names(LXC_PATH).unwrap();
+    assert!(!names.iter().any(|n| n == "morbier"));
+
+    // Verify that it is listed as active once started
+    ct.start().unwrap();
+
+    let active = Container::list_active(LXC_PATH).unwrap();
+    assert!(active.iter().any(|c| c.name == "morbier"));
+
+    ct.stop().unwrap();
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn default_lxcpath() {
+    assert!(!super::default_lxcpath().unwrap().is_empty());
+}
+
+#[test]
+fn create_get_default_container() {
+    // Create a container in the default lxcpath
+    let ct = Container::create_default("cantal", Template::new("debian")).unwrap();
+
+    // Verify that it can be found through the default lxcpath
+    let found = Container::get_default("cantal").unwrap();
+    assert_eq!(found.name.as_str(), "cantal");
+
+    // Verify that the multi-path listing reports its lxcpath
+    let lxcpath = super::default_lxcpath().unwrap();
+    let names = Container::list_names_in(&[lxcpath.as_str()]).unwrap();
+    assert!(names.contains(&(lxcpath.clone(), "cantal".to_string())));
+
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn global_config() {
+    // Verify that items with built-in defaults are resolved
+    assert!(!GlobalConfig::lxcpath().unwrap().is_empty());
+    assert!(!GlobalConfig::default_config().unwrap().is_empty());
+
+    // Verify that the generic getter agrees with the typed ones
+    assert_eq!(GlobalConfig::get("lxc.lxcpath"), GlobalConfig::lxcpath());
+
+    // Verify that unknown keys are rejected
+    assert!(GlobalConfig::get("lxc.does.not.exist").is_none());
+}
+
+#[test]
+fn version_parse() {
+    let v = Version::parse("2.1.0").unwrap();
+    assert_eq!(v, Version::new(2, 1, 0));
+
+    let v = Version::parse("3.1.0-devel").unwrap();
+    assert_eq!((v.major, v.minor, v.micro, v.devel), (3, 1, 0, true));
+
+    let v = Version::parse("3.0.0.beta1").unwrap();
+    assert_eq!((v.major, v.minor, v.micro, v.devel), (3, 0, 0, true));
+
+    assert!(Version::parse("devel").is_none());
+}
+
+#[test]
+fn version_ordering() {
+    assert!(Version::new(2, 1, 0) < Version::new(3, 0, 0));
+    assert!(Version::new(3, 0, 1) > Version::new(3, 0, 0));
+    assert!(Version::parse("3.0.0-devel").unwrap() < Version::new(3, 0, 0));
+}
+
+#[test]
+fn version_current() {
+    assert!(Version::current().is_some());
+}
+
+#[test]
+fn feature_detection() {
+    assert!(super::config_item_supported("lxc.rootfs.path") || super::config_item_supported("lxc.rootfs"));
+    assert!(!super::config_item_supported("lxc.does.not.exist"));
+    assert!(!super::has_api_extension("does_not_exist"));
+}
+
+#[test]
+fn init() {
+    super::init().unwrap();
+}
+
+#[test]
+fn create_share_container_between_threads() {
+    // Create a container
+    let ct = Container::create(LXC_PATH, "beaufort", Template::new("debian")).unwrap();
+
+    // Verify that clones of the handle can be used from other threads
+    let handles = (0..4).map(|_| {
+        let ct = ct.clone();
+        std::thread::spawn(move || ct.state().unwrap().to_string())
+    }).collect::<Vec<_>>();
+
+    for handle in handles {
+        assert_eq!(handle.join().unwrap().as_str(), "STOPPED");
+    }
+
+    // Verify that the original handle is still usable
+    ct.start().unwrap();
+    assert!(ct.is_running().unwrap());
+
+    ct.stop().unwrap();
+    ct.destroy().unwrap();
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_create_start_shutdown_container() {
+    use super::AsyncContainer;
+    use std::time::Duration;
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    rt.block_on(async {
+        // Create and start a container
+        let ct = AsyncContainer::create(LXC_PATH, "saint-nectaire", Template::new("debian")).await.unwrap();
+        ct.start().await.unwrap();
+
+        // Verify that waiting for its state works
+        ct.wait("RUNNING", Some(Duration::from_secs(10))).await.unwrap();
+
+        // Verify that it can be shut down
+        ct.shutdown_or_stop(Duration::from_secs(30)).await.unwrap();
+        assert_eq!(ct.container().state().unwrap(), "STOPPED");
+
+        ct.into_inner().destroy().unwrap();
+    });
+}
+
+#[test]
+fn state_names() {
+    assert_eq!("RUNNING".parse::<State>().unwrap(), State::Running);
+    assert_eq!(State::Frozen.to_string().as_str(), "FROZEN");
+    assert!("running".parse::<State>().is_err());
+}
+
+#[test]
+fn monitor_decode_messages() {
+    use super::monitor::MSG_SIZE;
+    use std::convert::TryInto;
+
+    // Build two struct lxc_msg back to back: type, name, value, pid
+    let message = |kind: i32, name: &str, value: i32, pid: i32| {
+        let mut msg = kind.to_ne_bytes().to_vec();
+        msg.extend(name.as_bytes());
+        msg.resize(4 + 256, 0);
+        msg.extend(&value.to_ne_bytes());
+        msg.extend(&pid.to_ne_bytes());
+        msg
+    };
+
+    let mut stream = message(0, "comte", 2, 1234);
+    stream.extend(message(2, "comte", 256, 1234));
+    assert_eq!(stream.len(), 2 * MSG_SIZE);
+
+    let events = stream.chunks_exact(MSG_SIZE)
+        .map(|chunk| Event::decode(chunk.try_into().unwrap()))
+        .collect::<Vec<Option<Event>>>();
+
+    assert_eq!(events, vec![
+        Some(Event::State { name: "comte".to_string(), state: State::Running }),
+        Some(Event::Exit { name: "comte".to_string(), status: 256 })
+    ]);
+}
+
+#[test]
+fn create_start_monitor_container() {
+    // Listen to the events of a new container
+    let ct = Container::create(LXC_PATH, "comte", Template::new("debian")).unwrap();
+    let monitor = Monitor::open_container(LXC_PATH, "comte").unwrap();
+
+    // Verify that starting it produces state events
+    ct.start().unwrap();
+
+    let states = monitor
+        .filter_map(|ev| match ev.unwrap() {
+            Event::State { state, .. } => Some(state),
+            Event::Exit { .. } => None
+        })
+        .take_while(|state| *state != State::Running)
+        .collect::<Vec<State>>();
+
+    assert!(states.contains(&State::Starting));
+
+    ct.stop().unwrap();
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn create_start_migrate_container() {
+    // Create and start a container
+    let ct = Container::create(LXC_PATH, "vacherin", Template::new("debian")).unwrap();
+    ct.start().unwrap();
+
+    // Pre-dump the container's memory, then dump the pages
+    // modified since and stop the container
+    let predump = MigrateOptions::new("/tmp/vacherin/predump");
+    ct.migrate(MigrateCommand::PreDump, &predump).unwrap();
+
+    let dump = MigrateOptions::new("/tmp/vacherin/dump")
+        .predump_dir("../predump")
+        .stop(true);
+    ct.migrate(MigrateCommand::Dump, &dump).unwrap();
+    assert!(!ct.is_running().unwrap());
+
+    // Restore the container from the dump
+    ct.migrate(MigrateCommand::Restore, &MigrateOptions::new("/tmp/vacherin/dump")).unwrap();
+    assert!(ct.is_running().unwrap());
+
+    // Stop and destroy the container
+    ct.stop().unwrap();
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn criu_features_set() {
+    let features = CriuFeatures::MEM_TRACK | CriuFeatures::LAZY_PAGES;
+
+    assert_eq!(features, CriuFeatures::all());
+    assert!(features.contains(CriuFeatures::LAZY_PAGES));
+    assert!(!CriuFeatures::MEM_TRACK.contains(features));
+    assert!(CriuFeatures::empty().is_empty());
+}
+
+#[test]
+fn create_check_criu_features() {
+    // Create a container
+    let ct = Container::create(LXC_PATH, "cancoillotte", Template::new("debian")).unwrap();
+
+    // Verify that only requested features are reported
+    let available = ct.criu_features(CriuFeatures::MEM_TRACK).unwrap();
+    assert!(CriuFeatures::MEM_TRACK.contains(available));
+
+    // Destroy the container
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn create_with_backing_store_container() {
+    // Create a container on a directory outside of its lxcpath
+    let options = CreateOptions::new()
+        .backing_store(BackingStore::Dir)
+        .dir("/tmp/abondance-rootfs");
+
+    let ct = Container::create_with(LXC_PATH, "abondance", Template::new("debian"), &options).unwrap();
+
+    // Verify that the rootfs was placed in that directory
+    let rootfs = ct.get_config_item(create::ROOTFS_KEY).unwrap();
+    assert!(rootfs.contains("/tmp/abondance-rootfs"));
+
+    // Destroy it
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn create_capture_template_output() {
+    // Create a container, capturing the output of its template
+    let output = Arc::new(Mutex::new(String::new()));
+    let options = CreateOptions::new().output_buffer(output.clone());
+
+    let ct = Container::create_with(LXC_PATH, "brie", Template::new("debian"), &options).unwrap();
+
+    // Verify that the template's output was captured
+    assert!(!output.lock().unwrap().is_empty());
+
+    // Destroy it
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn create_from_rootfs_container() {
+    // Build a minimal rootfs
+    std::fs::create_dir_all("/tmp/mimolette-rootfs/etc").unwrap();
+    std::fs::write("/tmp/mimolette-rootfs/etc/hostname", "mimolette\n").unwrap();
+
+    // Create a container from it, without any template
+    let rootfs = Rootfs::Directory("/tmp/mimolette-rootfs".to_string());
+    let ct = Container::create_from_rootfs(LXC_PATH, "mimolette", &rootfs, &CreateOptions::new()).unwrap();
+
+    // Verify that the rootfs was copied
+    let hostname = std::fs::read_to_string("/var/lib/lxc/mimolette/rootfs/etc/hostname").unwrap();
+    assert_eq!(hostname.as_str(), "mimolette\n");
+
+    // Destroy it
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn create_snapshot_metadata_container() {
+    // Create a container and a snapshot with a comment
+    let ct = Container::create(LXC_PATH, "epoisses", Template::new("debian")).unwrap();
+
+    std::fs::write("/tmp/epoisses-comment", "before upgrade\n").unwrap();
+    ct.snapshot(Some("/tmp/epoisses-comment")).unwrap();
+
+    // Verify the snapshot's metadata
+    let snaps = ct.snapshot_list().unwrap();
+    let snap = &snaps[0];
+
+    assert_eq!(snap.comment.as_deref(), Some("before upgrade\n"));
+    assert_eq!(snap.lxcpath.as_str(), "/var/lib/lxc/epoisses/snaps");
+    assert!(snap.created_at().unwrap() <= std::time::SystemTime::now());
+
+    // Verify that the snapshot's container can be inspected
+    let snap_ct = snap.open().unwrap();
+    assert_eq!(snap_ct.name(), "snap0");
+    assert!(snap_ct.rootfs().unwrap().starts_with("/var/lib/lxc/epoisses/snaps/snap0"));
+
+    // Destroy the container and its snapshot
+    ct.destroy_with_snapshots().unwrap();
+}
+
+#[test]
+fn create_snapshot_with_comment_container() {
+    // Create a container
+    let ct = Container::create(LXC_PATH, "munster", Template::new("debian")).unwrap();
+
+    // Take snapshots with inline comments and tags
+    let first = ct.snapshot_with_comment("before upgrade").unwrap();
+    let second = ct.snapshot_with_tags(None, &["daily", "verified"]).unwrap();
+
+    assert_eq!(first.as_str(), "snap0");
+    assert_eq!(second.as_str(), "snap1");
+
+    // Verify that the listing contains the comment and tags
+    let snaps = ct.snapshot_list().unwrap();
+
+    let snap = snaps.iter().find(|s| s.name == first).unwrap();
+    assert_eq!(snap.comment.as_deref(), Some("before upgrade"));
+
+    let snap = snaps.iter().find(|s| s.name == second).unwrap();
+    assert_eq!(snap.tags, vec!["daily".to_string(), "verified".to_string()]);
+
+    // Destroy the container and its snapshots
+    ct.destroy_with_snapshots().unwrap();
+}
+
+#[test]
+fn snapshot_comment_dir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Verify that the comment is written in a private directory
+    let dir = snapshot::CommentDir::new().unwrap();
+    let path = dir.write("Before upgrade").unwrap();
+
+    let parent = std::path::Path::new(&path).parent().unwrap().to_path_buf();
+    assert_eq!(std::fs::metadata(&parent).unwrap().permissions().mode() & 0o777, 0o700);
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    assert_eq!(std::fs::read_to_string(&path).unwrap().as_str(), "Before upgrade");
+
+    // Verify that an existing file is never overwritten
+    assert!(dir.write("Again").is_err());
+
+    // Verify that it is removed afterwards
+    drop(dir);
+    assert!(!parent.exists());
+}
+
+/// Number of calls to `mock_snapshot_free`.
+static MOCK_SNAPSHOT_FREES: AtomicUsize = AtomicUsize::new(0);
+
+/// Mock of liblxc's snapshot free function, which frees the strings
+/// of an entry but not the entry itself.
+unsafe extern "C" fn mock_snapshot_free(snap: *mut lib::lxc_snapshot) {
+    MOCK_SNAPSHOT_FREES.fetch_add(1, Ordering::SeqCst);
+
+    libc::free((*snap).name as *mut c_void);
+    libc::free((*snap).comment_pathname as *mut c_void);
+    libc::free((*snap).timestamp as *mut c_void);
+    libc::free((*snap).lxcpath as *mut c_void);
+}
+
+#[test]
+fn snapshot_list_memory() {
+    // Build an array the way liblxc's snapshot_list does
+    let count = 3;
+    let snaps = unsafe {
+        libc::malloc(count * std::mem::size_of::<lib::lxc_snapshot>()) as *mut lib::lxc_snapshot
+    };
+
+    for i in 0..count {
+        let name = CString::new(format!("snap{}", i)).unwrap();
+        let timestamp = CString::new("2018:01:02 03:04:05").unwrap();
+        let lxcpath = CString::new("/var/lib/lxc/gruyere/snaps").unwrap();
+
+        unsafe {
+            snaps.add(i).write(lib::lxc_snapshot {
+                name: libc::strdup(name.as_ptr()),
+                comment_pathname: std::ptr::null_mut(),
+                timestamp: libc::strdup(timestamp.as_ptr()),
+                lxcpath: libc::strdup(lxcpath.as_ptr()),
+                free: Some(mock_snapshot_free)
+            });
+        }
+    }
+
+    // Verify that each entry is copied and freed exactly once, and
+    // that the array is released with libc's allocator
+    let list = unsafe { snapshot::snapshots_from_raw(snaps, count) };
+    assert_eq!(MOCK_SNAPSHOT_FREES.load(Ordering::SeqCst), count);
+
+    assert_eq!(list.len(), count);
+    assert_eq!(list[2].name.as_str(), "snap2");
+    assert_eq!(list[0].created.as_str(), "2018:01:02 03:04:05");
+    assert!(list[0].comment.is_none());
+
+    // Snapshots own their data, dropping them frees nothing else
+    drop(list);
+    assert_eq!(MOCK_SNAPSHOT_FREES.load(Ordering::SeqCst), count);
+}
+
+/// Build a snapshot created at the given local time.
+fn snapshot_at(name: &str, created: &str) -> Snapshot {
+    Snapshot {
+        name: name.to_string(),
+        created: created.to_string(),
+        comment: None,
+        tags: Vec::new(),
+        lxcpath: "/var/lib/lxc/raclette/snaps".to_string()
+    }
+}
+
+#[test]
+fn retention_policy_plan() {
+    let snaps = vec![
+        snapshot_at("snap0", "2018:01:01 10:00:00"),
+        snapshot_at("snap1", "2018:01:02 10:00:00"),
+        snapshot_at("snap2", "2018:01:03 09:00:00"),
+        snapshot_at("snap3", "2018:01:03 10:00:00"),
+        snapshot_at("snap4", "2018:01:03 10:30:00"),
+        snapshot_at("snap5", "2018:01:03 11:00:00"),
+        snapshot_at("snap6", "invalid")
+    ];
+
+    // Without any rule, everything is kept
+    let plan = RetentionPolicy::new().plan(&snaps);
+    assert_eq!(plan.keep.len(), snaps.len());
+    assert!(plan.prune.is_empty());
+
+    // Keep the last one, plus the newest of each of the last 3 hours
+    let plan = RetentionPolicy::new().keep_last(1).keep_hourly(3).plan(&snaps);
+    assert_eq!(plan.keep, vec!["snap6", "snap5", "snap4", "snap2"]);
+    assert_eq!(plan.prune, vec!["snap3", "snap1", "snap0"]);
+
+    // Keep the newest of each of the last 2 days
+    let plan = RetentionPolicy::new().keep_daily(2).plan(&snaps);
+    assert_eq!(plan.keep, vec!["snap6", "snap5", "snap1"]);
+    assert_eq!(plan.prune, vec!["snap4", "snap3", "snap2", "snap0"]);
+
+    // 2018-01-01 was a monday: all of them are in the same week
+    let plan = RetentionPolicy::new().keep_weekly(4).plan(&snaps);
+    assert_eq!(plan.keep, vec!["snap6", "snap5"]);
+}
+
+#[test]
+fn create_snapshot_prune_container() {
+    // Create a container with a few snapshots
+    let ct = Container::create(LXC_PATH, "tamie", Template::new("debian")).unwrap();
+
+    for _ in 0..3 {
+        ct.snapshot(None).unwrap();
+    }
+
+    // Verify that a dry run does not destroy anything
+    let policy = RetentionPolicy::new().keep_last(1);
+    let plan = ct.snapshot_prune(&policy, true).unwrap();
+
+    assert_eq!(plan.keep.len(), 1);
+    assert_eq!(plan.prune.len(), 2);
+    assert_eq!(ct.snapshot_list().unwrap().len(), 3);
+
+    // Verify that the plan is applied
+    ct.snapshot_and_prune(&policy).unwrap();
+    assert_eq!(ct.snapshot_list().unwrap().len(), 1);
+
+    // Destroy the container and its snapshots
+    ct.destroy_with_snapshots().unwrap();
+}
+
+#[test]
+fn create_snapshot_clone_container() {
+    // Create a container and take a snapshot of it
+    let ct = Container::create(LXC_PATH, "neufchatel", Template::new("debian")).unwrap();
+    ct.snapshot(None).unwrap();
+
+    // Clone a new container from the snapshot
+    let snaps = ct.snapshot_list().unwrap();
+    let clone = snaps[0].clone_to("salers", LXC_PATH, None, false).unwrap();
+
+    assert_eq!(clone.name.as_str(), "salers");
+    assert!(Container::exists(LXC_PATH, "salers"));
+
+    // Verify that the clone runs independently of the container
+    clone.start().unwrap();
+    assert!(!ct.is_running().unwrap());
+    clone.stop().unwrap();
+
+    // Destroy both containers
+    clone.destroy().unwrap();
+    ct.destroy_with_snapshots().unwrap();
+}
+
+#[test]
+fn idmap_parse_validate() {
+    // Verify the lxc.idmap entries format
+    let map = IdMap::parse("u 0 100000 65536").unwrap();
+    assert_eq!(map, IdMap::uid(0, 100000, 65536));
+    assert_eq!(map.to_string().as_str(), "u 0 100000 65536");
+    assert!(IdMap::parse("x 0 100000 65536").is_none());
+    assert!(IdMap::parse("g 0 100000").is_none());
+
+    // Verify the mapping of ids
+    assert_eq!(map.to_host(0), Some(100000));
+    assert_eq!(map.to_host(65535), Some(165535));
+    assert_eq!(map.to_host(65536), None);
+
+    // Verify the detection of overlapping ranges
+    let uids = [IdMap::uid(0, 100000, 1000), IdMap::uid(1000, 101000, 1000)];
+    assert!(IdMap::validate(&uids).is_ok());
+
+    let mixed = [IdMap::uid(0, 100000, 1000), IdMap::gid(0, 100000, 1000)];
+    assert!(IdMap::validate(&mixed).is_ok());
+
+    let overlapping = [IdMap::uid(0, 100000, 1000), IdMap::uid(1000, 100500, 1000)];
+    assert!(IdMap::validate(&overlapping).is_err());
+    assert!(IdMap::validate(&[IdMap::gid(0, 100000, 0)]).is_err());
+}
+
+#[test]
+fn idmap_subids() {
+    let content = "root:100000:65536\ncomte:165536:1000\ncomte:200000:5000\n";
+
+    let maps = IdMap::parse_subids(IdKind::Uid, content, "comte");
+    assert_eq!(maps, vec![IdMap::uid(0, 165536, 1000), IdMap::uid(1000, 200000, 5000)]);
+    assert!(IdMap::parse_subids(IdKind::Gid, content, "morbier").is_empty());
+}
+
+#[test]
+fn create_set_idmaps_container() {
+    // Create a container
+    let ct = Container::create(LXC_PATH, "ossau", Template::new("debian")).unwrap();
+
+    // Verify that the mappings are written to its configuration
+    let maps = [IdMap::uid(0, 100000, 65536), IdMap::gid(0, 100000, 65536)];
+    ct.set_idmaps(&maps).unwrap();
+    assert_eq!(ct.idmaps().unwrap(), maps.to_vec());
+
+    // Verify that its rootfs can be shifted, twice
+    ct.shift_rootfs().unwrap();
+    ct.shift_rootfs().unwrap();
+
+    // Destroy it
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn capability_names() {
+    assert_eq!("sys_admin".parse::<Capability>().unwrap(), Capability::SysAdmin);
+    assert_eq!("CAP_NET_RAW".parse::<Capability>().unwrap(), Capability::NetRaw);
+    assert_eq!(Capability::MacAdmin.to_string().as_str(), "mac_admin");
+    assert!("sys_everything".parse::<Capability>().is_err());
+    assert_eq!(Capability::all().len(), 41);
+
+    // Verify that recent capabilities are written by number
+    let caps = [Capability::SysAdmin, Capability::Bpf, Capability::CheckpointRestore];
+    assert_eq!(security::format_caps(&caps).as_str(), "sys_admin 39 40");
+    assert_eq!(security::parse_caps("sys_admin 39 40").unwrap(), caps.to_vec());
+    assert_eq!(security::parse_caps("0 12").unwrap(), vec![Capability::Chown, Capability::NetAdmin]);
+    assert!(security::parse_caps("sys_admin 41").is_err());
+}
+
+#[test]
+fn create_security_config_container() {
+    // Create a container
+    let ct = Container::create(LXC_PATH, "chaource", Template::new("debian")).unwrap();
+
+    // Verify the LSM and no_new_privs settings
+    ct.set_apparmor_profile("unconfined").unwrap();
+    assert_eq!(ct.apparmor_profile().unwrap().as_str(), "unconfined");
+
+    ct.set_no_new_privs(true).unwrap();
+    assert!(ct.no_new_privs().unwrap());
+
+    // Verify that capabilities can be dropped, but not kept as well
+    ct.set_cap_drop(&[Capability::SysModule, Capability::MacAdmin]).unwrap();
+    assert_eq!(ct.cap_drop().unwrap(), vec![Capability::SysModule, Capability::MacAdmin]);
+    assert!(ct.set_cap_keep(&[Capability::Chown]).is_err());
+
+    // Verify that keeping is allowed once nothing is dropped
+    ct.set_cap_drop(&[]).unwrap();
+    ct.set_cap_keep(&[Capability::Chown]).unwrap();
+    assert_eq!(ct.cap_keep().unwrap(), vec![Capability::Chown]);
+
+    // Destroy it
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn seccomp_policy_format() {
+    // Verify the generated policy
+    let policy = SeccompPolicy::denylist()
+        .deny_module_loading()
+        .rule(SeccompArch::X86_64, SeccompRule::new("personality")
+            .action(SeccompAction::Kill)
+            .arg(SeccompArg::new(0, 8, SeccompCompare::Eq)))
+        .notify("mknod");
+
+    let content = policy.to_string();
+    assert!(content.starts_with("2\nblacklist\n[all]\ninit_module errno 1\n"));
+    assert!(content.contains("mknod notify\n[x86_64]\npersonality kill [0,8,SCMP_CMP_EQ]\n"));
+
+    // Verify that it is parsed back identically
+    let parsed = content.parse::<SeccompPolicy>().unwrap();
+    assert_eq!(parsed, policy);
+    assert_eq!(parsed.rules(SeccompArch::X86_64).len(), 1);
+
+    // Verify that the header action applies to the unlisted calls
+    let allowlist = SeccompPolicy::allowlist().default_action(SeccompAction::Errno(38)).allow("read");
+    assert_eq!(allowlist.to_string().as_str(), "2\nwhitelist errno 38\n[all]\nread allow\n");
+
+    // Verify that the presets are valid, and only deny the listed calls
+    assert!(SeccompPolicy::lxc_default().validate().is_ok());
+    assert!(SeccompPolicy::lxc_default().to_string().starts_with("2\nblacklist\n[all]\nreject_force_umount\n"));
+    assert!(SeccompPolicy::allowlist().deny_keyctl().validate().is_ok());
+}
+
+#[test]
+fn seccomp_policy_parse() {
+    // Verify the parsing of a policy written by hand
+    let content = "2\n# Comment\nwhitelist kill\nread\nwrite # Comment\n[ARM64]\n\
+                   ioctl allow [1,0x5401,SCMP_CMP_MASKED_EQ,0xffff]\n";
+    let policy = content.parse::<SeccompPolicy>().unwrap();
+
+    assert_eq!(policy.mode(), SeccompMode::Allowlist);
+    assert_eq!(policy.rules(SeccompArch::All).len(), 2);
+
+    let rule = &policy.rules(SeccompArch::Arm64)[0];
+    assert_eq!(rule.action, Some(SeccompAction::Allow));
+    assert_eq!(rule.args[0], SeccompArg::new(1, 0x5401, SeccompCompare::MaskedEq(0xffff)));
+
+    // Verify the detection of invalid policies
+    assert!("1\nblacklist\n".parse::<SeccompPolicy>().is_err());
+    assert!("2\ngraylist\n".parse::<SeccompPolicy>().is_err());
+    assert!("2\nblacklist\n[vax]\n".parse::<SeccompPolicy>().is_err());
+
+    // Verify that every architecture liblxc knows is accepted
+    for arch in SeccompArch::all() {
+        assert_eq!(arch.as_str().parse::<SeccompArch>().unwrap(), *arch);
+    }
+
+    assert_eq!("X86".parse::<SeccompArch>().unwrap(), SeccompArch::I386);
+    assert_eq!("i386".parse::<SeccompArch>().unwrap(), SeccompArch::I386);
+    assert!("2\nblacklist\n[mipsel64n32]\nkeyctl\n[PPC]\nkeyctl\n".parse::<SeccompPolicy>().is_ok());
+    assert!("2\nblacklist\nkeyctl errno 5000\n".parse::<SeccompPolicy>().is_err());
+    assert!("2\nblacklist\nkeyctl errno 1 [6,0,SCMP_CMP_EQ]\n".parse::<SeccompPolicy>().is_err());
+    assert!("2\nblacklist\nkeyctl errno 1 [0,0,SCMP_CMP_XOR]\n".parse::<SeccompPolicy>().is_err());
+}
+
+#[test]
+fn create_seccomp_policy_container() {
+    // Create a container
+    let ct = Container::create(LXC_PATH, "fourme", Template::new("debian")).unwrap();
+
+    // Verify that a generated policy is attached and can be started with
+    let policy = SeccompPolicy::lxc_default().deny_keyctl();
+    ct.set_seccomp_policy(&policy, "/tmp/fourme.seccomp").unwrap();
+    assert_eq!(ct.seccomp_profile().unwrap().as_str(), "/tmp/fourme.seccomp");
+
+    ct.start().unwrap();
+    ct.stop().unwrap();
+
+    // Destroy it
+    ct.destroy().unwrap();
+}
+
+#[test]
+#[cfg(feature = "v4_0")]
+fn seccomp_response_raw() {
+    use super::notify::{SeccompNotif, SeccompNotifResp};
+    use super::SeccompResponse;
+
+    // The structs must match the kernel's layout
+    assert_eq!(std::mem::size_of::<SeccompNotif>(), 80);
+    assert_eq!(std::mem::size_of::<SeccompNotifResp>(), 24);
+
+    let allow = SeccompResponse::Allow.to_raw(7).unwrap();
+    assert_eq!((allow.id, allow.val, allow.error, allow.flags), (7, 0, 0, 1));
+
+    let deny = SeccompResponse::Deny(libc::EPERM).to_raw(8).unwrap();
+    assert_eq!((deny.id, deny.val, deny.error, deny.flags), (8, 0, -libc::EPERM, 0));
+    assert!(SeccompResponse::Deny(0).to_raw(8).is_err());
+
+    let emulate = SeccompResponse::Emulate(3).to_raw(9).unwrap();
+    assert_eq!((emulate.id, emulate.val, emulate.error, emulate.flags), (9, 3, 0, 0));
+}
+
+#[test]
+#[cfg(feature = "v4_0")]
+fn create_seccomp_supervisor_container() {
+    use super::SeccompResponse;
+    use std::os::unix::io::AsRawFd;
+
+    // Create a container forwarding mknod to a supervisor
+    let ct = Container::create(LXC_PATH, "livarot", Template::new("debian")).unwrap();
+
+    let policy = SeccompPolicy::lxc_default().notify("mknod").notify("mknodat");
+    ct.set_seccomp_policy(&policy, "/tmp/livarot.seccomp").unwrap();
+    ct.want_daemonize(true).unwrap();
+    ct.start().unwrap();
+
+    // Verify that the monitor hands a notify fd out
+    let supervisor = ct.seccomp_supervisor().unwrap();
+    assert!(supervisor.as_raw_fd() >= 0);
+
+    // Deny every forwarded call until the container stops
+    let handle = std::thread::spawn(move || {
+        supervisor.run(|_, _| SeccompResponse::Deny(libc::EPERM))
+    });
+
+    ct.stop().unwrap();
+    handle.join().unwrap().unwrap();
+
+    // Destroy it
+    ct.destroy().unwrap();
+}
+
+#[test]
+fn hook_names() {
+    assert_eq!(Hook::PreStart.key().as_str(), "lxc.hook.pre-start");
+    assert_eq!(Hook::all().len(), 10);
+
+    for hook in Hook::all() {
+        assert_eq!(hook.to_string().parse::<Hook>().unwrap(), *hook);
+        assert_eq!(hook.key().parse::<Hook>().unwrap(), *hook);
+    }
+
+    assert!("lxc.hook.poststop".parse::<Hook>().is_err());
+}
+
+#[test]
+fn create_hooks_container() {
+    // Create a container
+    let ct = Container::create(LXC_PATH, "rocamadour", Template::new("debian")).unwrap();
+
+    // Verify that hooks are listed in the order they are run
+    ct.add_hook(Hook::PostStop, "/usr/local/bin/cleanup").unwrap();
+    ct.add_hook(Hook::PreStart, "/usr/local/bin/prepare").unwrap();
+    ct.add_hook(Hook::PreStart, "/usr/local/bin/audit").unwrap();
+
+    assert_eq!(ct.hooks().unwrap(), vec![
+        (Hook::PreStart, "/usr/local/bin/prepare".to_string()),
+        (Hook::PreStart, "/usr/local/bin/audit".to_string()),
+        (Hook::PostStop, "/usr/local/bin/cleanup".to_string())
+    ]);
+
+    ct.clear_hooks(Hook::PreStart).unwrap();
+    assert_eq!(ct.hooks().unwrap(), vec![(Hook::PostStop, "/usr/local/bin/cleanup".to_string())]);
+
+    // Verify that hooks can get their arguments from the environment
+    if cfg!(feature = "v3_0") {
+        ct.set_hook_version(HookVersion::Environment).unwrap();
+        assert_eq!(ct.hook_version().unwrap(), HookVersion::Environment);
+    }
+
+    // Destroy it
+    ct.destroy().unwrap();
+}
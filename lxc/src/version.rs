@@ -0,0 +1,83 @@
+//! Parsed representation of liblxc's version string.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Version of liblxc, comparable so that callers can gate
+/// behavior on the version in use. Development and pre-release
+/// versions are ordered before the release with the same number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Version {
+    /// Major version number.
+    pub major: u32,
+
+    /// Minor version number.
+    pub minor: u32,
+
+    /// Micro (patch) version number.
+    pub micro: u32,
+
+    /// Wether this is a development or pre-release version.
+    pub devel: bool
+}
+
+impl Version {
+    /// Create a new release Version object.
+    pub fn new(major: u32, minor: u32, micro: u32) -> Version {
+        Version {
+            major,
+            minor,
+            micro,
+            devel: false
+        }
+    }
+
+    /// Parse a liblxc version string such as `2.1.0`,
+    /// `3.0.0.beta1` or `3.1.0-devel`.
+    pub fn parse(s: &str) -> Option<Version> {
+        let end = s.find(|c: char| c != '.' && !c.is_ascii_digit()).unwrap_or(s.len());
+        let (numbers, suffix) = s.split_at(end);
+
+        let mut parts = numbers.trim_end_matches('.').split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+        let micro = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+
+        Some(Version {
+            major,
+            minor,
+            micro,
+            devel: !suffix.is_empty()
+        })
+    }
+
+    /// Determine the version of the liblxc currently in use.
+    pub fn current() -> Option<Version> {
+        Version::parse(super::version())
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        (self.major, self.minor, self.micro, !self.devel)
+            .cmp(&(other.major, other.minor, other.micro, !other.devel))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.micro)?;
+
+        if self.devel {
+            write!(f, "-devel")?;
+        }
+
+        Ok(())
+    }
+}